@@ -0,0 +1,179 @@
+use std::ffi::OsStr;
+
+use clap_complete::engine::CompletionCandidate;
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    workload::{self, Workload},
+};
+
+/// Minimum similarity for a name to be suggested in place of an unknown one
+const SUGGESTION_THRESHOLD: f64 = 0.6;
+
+/// Workload Catalog
+/// The workloads available in the etna repository, used to validate and complete
+/// the languages and workloads given on the command line.
+#[derive(Debug, Default)]
+pub(crate) struct Catalog {
+    pub workloads: Vec<Workload>,
+}
+
+impl Catalog {
+    pub(crate) fn load(etna_config: &EtnaConfig) -> anyhow::Result<Self> {
+        let workloads = workload::available(&etna_config.workloads_repo_dir())?;
+
+        Ok(Self { workloads })
+    }
+
+    /// The catalog of the configured etna repository, if there is one
+    fn current() -> Option<Self> {
+        let etna_config = EtnaConfig::get_etna_config().ok()?;
+        Self::load(&etna_config).ok()
+    }
+
+    pub(crate) fn languages(&self) -> Vec<&str> {
+        let mut languages = self
+            .workloads
+            .iter()
+            .map(|w| w.language.as_str())
+            .collect::<Vec<_>>();
+        languages.dedup();
+        languages
+    }
+
+    /// Names of the workloads of a language, or of every language
+    pub(crate) fn names(&self, language: Option<&str>) -> Vec<&str> {
+        let mut names = self
+            .workloads
+            .iter()
+            .filter(|w| language.is_none_or(|l| w.language.eq_ignore_ascii_case(l)))
+            .map(|w| w.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The language as it is named in the etna repository
+    pub(crate) fn language(&self, language: &str) -> anyhow::Result<String> {
+        let languages = self.languages();
+
+        match find(&languages, language) {
+            Some(language) => Ok(language.to_string()),
+            None => anyhow::bail!(
+                "Unknown language '{language}'{}",
+                hint(language, &languages)
+            ),
+        }
+    }
+
+    /// The language as the experiment, etna or a workload template names it. Languages of
+    /// imported workloads are only known to the experiment.
+    pub(crate) fn known_language(
+        &self,
+        experiment_config: &ExperimentConfig,
+        language: &str,
+    ) -> anyhow::Result<String> {
+        let mut languages: Vec<&str> = vec![];
+        for known in experiment_config
+            .workloads
+            .iter()
+            .map(|w| w.language.as_str())
+            .chain(self.languages())
+            .chain(workload::LANGUAGES)
+        {
+            if !languages.contains(&known) {
+                languages.push(known);
+            }
+        }
+
+        match find(&languages, language) {
+            Some(language) => Ok(language.to_string()),
+            None => anyhow::bail!(
+                "Unknown language '{language}'{}",
+                hint(language, &languages)
+            ),
+        }
+    }
+
+    /// The workload as it is named in the etna repository
+    pub(crate) fn workload(&self, language: &str, name: &str) -> anyhow::Result<Workload> {
+        let language = self.language(language)?;
+        let names = self.names(Some(&language));
+
+        match find(&names, name) {
+            Some(name) => Ok(Workload {
+                language,
+                name: name.to_string(),
+                source: None,
+            }),
+            None => anyhow::bail!(
+                "Unknown workload '{name}' for {language}{}",
+                hint(name, &names)
+            ),
+        }
+    }
+}
+
+/// Finds a name ignoring case
+fn find<'a>(names: &[&'a str], name: &str) -> Option<&'a str> {
+    names.iter().find(|n| n.eq_ignore_ascii_case(name)).copied()
+}
+
+/// The most similar of the candidates, if any is similar enough
+pub(crate) fn suggest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| {
+            // Names are short, so a transposition counts as a single edit
+            let similarity = strsim::normalized_damerau_levenshtein(
+                &name.to_lowercase(),
+                &candidate.to_lowercase(),
+            );
+            (candidate, similarity)
+        })
+        .filter(|(_, similarity)| *similarity >= SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(candidate, _)| *candidate)
+}
+
+/// A "did you mean" hint to append to an error message
+pub(crate) fn hint(name: &str, candidates: &[&str]) -> String {
+    match suggest(name, candidates) {
+        Some(suggestion) => format!(", did you mean '{suggestion}'?"),
+        None if candidates.is_empty() => String::new(),
+        None => format!(", expected one of {}", candidates.join(", ")),
+    }
+}
+
+pub(crate) fn complete_language(current: &OsStr) -> Vec<CompletionCandidate> {
+    let catalog = Catalog::current().unwrap_or_default();
+    candidates(&catalog.languages(), current)
+}
+
+/// Completes the workloads of the language given earlier on the command line, if any
+pub(crate) fn complete_workload(current: &OsStr) -> Vec<CompletionCandidate> {
+    let catalog = Catalog::current().unwrap_or_default();
+    let languages = catalog.languages();
+
+    // The command line being completed follows `--`, its last word is `current`
+    let words = std::env::args()
+        .skip_while(|a| a != "--")
+        .skip(1)
+        .collect::<Vec<_>>();
+    let language = words
+        .split_last()
+        .and_then(|(_, before)| before.iter().rev().find_map(|w| find(&languages, w)));
+
+    candidates(&catalog.names(language), current)
+}
+
+fn candidates(names: &[&str], current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy().to_lowercase();
+
+    names
+        .iter()
+        .filter(|name| name.to_lowercase().starts_with(&current))
+        .map(|name| CompletionCandidate::new(*name))
+        .collect()
+}
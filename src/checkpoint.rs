@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    config::EtnaConfig,
+    experiment::ExperimentSnapshot,
+    scheduler::{Outcome, Trial, TrialResult},
+};
+
+/// Progress of an experiment run, saved after every trial so an interrupted run
+/// can be resumed with `etna experiment run --resume`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    /// Snapshot of the experiment the trials were run against
+    pub snapshot: ExperimentSnapshot,
+    pub trials: Vec<TrialRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TrialRecord {
+    /// Id of the task, see [`Task::id`](crate::run::Task::id)
+    pub task: String,
+    pub trial: usize,
+    pub outcome: Outcome,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(snapshot: ExperimentSnapshot) -> Self {
+        Self {
+            snapshot,
+            trials: vec![],
+        }
+    }
+
+    /// Path of the checkpoint of an experiment
+    pub(crate) fn path(etna_config: &EtnaConfig, experiment_name: &str) -> PathBuf {
+        etna_config
+            .checkpoints_path()
+            .join(format!("{experiment_name}.json"))
+    }
+
+    /// Loads the checkpoint at `path`, if there is one
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read checkpoint '{}'", path.display()))?;
+        let checkpoint = serde_json::from_str(&content)
+            .context(format!("Failed to parse checkpoint '{}'", path.display()))?;
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Saves the checkpoint, replacing the previous one atomically so that an interrupted
+    /// write never loses the progress
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(format!(
+                "Failed to create checkpoint directory '{}'",
+                parent.display()
+            ))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        let temp = path.with_extension("json.tmp");
+
+        std::fs::write(&temp, content).context("Failed to write checkpoint file")?;
+        std::fs::rename(&temp, path).context("Failed to replace checkpoint file")
+    }
+
+    pub(crate) fn remove(path: &Path) -> anyhow::Result<()> {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .context(format!("Failed to remove checkpoint '{}'", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn record(&mut self, result: &TrialResult) {
+        self.trials.push(TrialRecord {
+            task: result.trial.task.id(),
+            trial: result.trial.trial,
+            outcome: result.outcome,
+        });
+    }
+
+    /// Whether a trial does not need to run again. Failed trials are retried, and the
+    /// remaining trials of a short-circuiting task are skipped once one of them timed out.
+    pub(crate) fn is_done(&self, trial: &Trial) -> bool {
+        let id = trial.task.id();
+
+        self.trials.iter().filter(|r| r.task == id).any(|r| {
+            (r.trial == trial.trial && r.outcome != Outcome::Failed)
+                || (trial.task.short_circuit && r.outcome == Outcome::TimedOut)
+        })
+    }
+}
@@ -1,10 +1,14 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{engine::ArgValueCompleter, env::CompleteEnv};
 
-use crate::commands;
+use crate::{catalog, commands, run::RunFilter};
 
 pub(crate) fn run() -> anyhow::Result<()> {
+    // Answers shell completion requests, e.g. `source <(COMPLETE=bash etna-cli)`
+    CompleteEnv::with_factory(Args::command).complete();
+
     let cli = Args::parse();
 
     match cli.command {
@@ -15,31 +19,88 @@ pub(crate) fn run() -> anyhow::Result<()> {
                 overwrite,
                 description,
             } => commands::experiment::new_experiment::invoke(name, path, overwrite, description),
-            ExperimentCommand::Run { name } => {
-                commands::experiment::run_experiment::invoke(name)
+            ExperimentCommand::Run {
+                name,
+                filter,
+                jobs,
+                resume,
+                force,
+                dry_run,
+            } => commands::experiment::run_experiment::invoke(
+                name,
+                filter.into(),
+                jobs,
+                resume,
+                force,
+                dry_run,
+            ),
+            ExperimentCommand::Plan { name, filter } => {
+                commands::experiment::plan_experiment::invoke(name, filter.into())
             }
             ExperimentCommand::Show {
                 hash_or_name,
                 is_name,
                 show_all,
             } => commands::experiment::show_experiment::invoke(hash_or_name, is_name, show_all),
+            ExperimentCommand::History { name } => {
+                commands::experiment::experiment_history::invoke(name)
+            }
         },
         Command::Workload(wl) => match wl {
             WorkloadCommand::AddWorkload {
                 experiment,
                 language,
                 workload,
-            } => commands::workload::add_workload::invoke(experiment, language, workload),
+                from,
+            } => commands::workload::add_workload::invoke(experiment, language, workload, from),
             WorkloadCommand::RemoveWorkload {
                 experiment,
                 language,
                 workload,
             } => commands::workload::remove_workload::invoke(experiment, language, workload),
+            WorkloadCommand::Mv {
+                experiment,
+                from,
+                to,
+            } => commands::workload::mv_workload::invoke(experiment, from, to),
             WorkloadCommand::ListWorkloads {
                 experiment,
                 language,
                 kind,
             } => commands::workload::list_workloads::invoke(experiment, language, kind),
+            WorkloadCommand::Check {
+                experiment,
+                workload,
+                build,
+            } => commands::workload::check_workload::invoke(experiment, workload, build),
+            WorkloadCommand::New {
+                experiment,
+                language,
+                name,
+            } => commands::workload::new_workload::invoke(experiment, language, name),
+            WorkloadCommand::Status { experiment } => {
+                commands::workload::status_workload::invoke(experiment)
+            }
+            WorkloadCommand::Update {
+                experiment,
+                language,
+                workload,
+            } => commands::workload::update_workload::invoke(experiment, language, workload),
+            WorkloadCommand::Variants {
+                experiment,
+                language,
+                workload,
+            } => commands::workload::variants_workload::invoke(experiment, language, workload),
+            WorkloadCommand::Materialize {
+                experiment,
+                language,
+                workload,
+                variant,
+                output,
+                overwrite,
+            } => commands::workload::materialize_variant::invoke(
+                experiment, language, workload, variant, output, overwrite,
+            ),
         },
         Command::Config(cl) => match cl {
             ConfigCommand::ChangeBranch { branch } => {
@@ -58,6 +119,10 @@ pub(crate) fn run() -> anyhow::Result<()> {
                 metric,
             } => commands::store::write::invoke(experiment_id, metric),
             StoreCommand::Query(query_option) => commands::store::query::invoke(query_option),
+            StoreCommand::Verify {
+                repair,
+                drop_dangling,
+            } => commands::store::verify::invoke(repair, drop_dangling),
         },
     }
 }
@@ -90,6 +155,35 @@ enum ExperimentCommand {
         /// [default: current directory]
         #[clap(short, long)]
         name: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Number of trials to run in parallel. Trials of the same workload only run in
+        /// parallel with the native runner, the others modify the workload in place.
+        #[clap(short, long, default_value = "1")]
+        jobs: usize,
+        /// Skip the trials finished by the last interrupted run
+        #[clap(long)]
+        resume: bool,
+        /// Resume even if the experiment changed since the interrupted run
+        #[clap(long, requires = "resume")]
+        force: bool,
+        /// Show the snapshot changes and the commands of the run without executing them.
+        /// Benchtool is not queried, so the variant and property pairs it does not expect to
+        /// break are listed as well
+        #[clap(long)]
+        dry_run: bool,
+    },
+    #[clap(
+        name = "plan",
+        about = "Show the tasks an experiment run would execute"
+    )]
+    Plan {
+        /// Name of the experiment to plan
+        /// [default: current directory]
+        #[clap(short, long)]
+        name: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
     },
     #[clap(name = "show", about = "Show the details of an experiment")]
     Show {
@@ -102,7 +196,50 @@ enum ExperimentCommand {
         #[clap(short = 'a', long, default_value = "false")]
         show_all: bool,
     },
+    #[clap(name = "history", about = "Show the snapshot history of an experiment")]
+    History {
+        /// Name of the experiment
+        name: String,
+    },
 }
+/// Narrows down the run matrix of an experiment
+#[derive(Debug, clap::Args)]
+struct FilterArgs {
+    /// Only run the given workloads, can be repeated
+    #[clap(long = "workload", add = ArgValueCompleter::new(catalog::complete_workload))]
+    workloads: Vec<String>,
+    /// Only run the given strategies, can be repeated
+    #[clap(long = "strategy")]
+    strategies: Vec<String>,
+    /// Only run the given mutant variants, can be repeated
+    #[clap(long = "variant")]
+    variants: Vec<String>,
+    /// Only run the given properties, can be repeated
+    #[clap(long = "property")]
+    properties: Vec<String>,
+    /// Number of trials for each task
+    /// [default: matrix.trials in config.toml]
+    #[clap(long)]
+    trials: Option<usize>,
+    /// Timeout of each trial in seconds
+    /// [default: matrix.timeout in config.toml]
+    #[clap(long)]
+    timeout: Option<f64>,
+}
+
+impl From<FilterArgs> for RunFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            workloads: args.workloads,
+            strategies: args.strategies,
+            variants: args.variants,
+            properties: args.properties,
+            trials: args.trials,
+            timeout: args.timeout,
+        }
+    }
+}
+
 #[derive(Debug, Subcommand)]
 enum WorkloadCommand {
     #[clap(name = "add", about = "Add a workload to the experiment")]
@@ -111,14 +248,16 @@ enum WorkloadCommand {
         /// [default: current directory]
         #[clap(short, long, default_value = None)]
         experiment: Option<String>,
-        /// Language of the workload
-        /// [default: coq]
-        /// [possible_values(coq, haskell, racket)]
+        /// Language of the workload, e.g. coq, haskell or racket
+        #[clap(add = ArgValueCompleter::new(catalog::complete_language))]
         language: String,
-        /// Workload to be added
-        /// [default: bst]
-        /// [possible_values(bst, rbt, stlc, ifc)]
+        /// Workload to be added, e.g. bst, rbt, stlc or ifc
+        #[clap(add = ArgValueCompleter::new(catalog::complete_workload))]
         workload: String,
+        /// Import the workload from a local directory or a git repository instead of etna,
+        /// optionally at a revision, e.g. `../heap` or `https://github.com/org/heap.git#v1.0`
+        #[clap(long)]
+        from: Option<String>,
     },
     #[clap(name = "remove", about = "Remove a workload from the experiment")]
     RemoveWorkload {
@@ -127,12 +266,26 @@ enum WorkloadCommand {
         #[clap(short, long, default_value = None)]
         experiment: Option<String>,
         /// Language of the workload
-        /// [possible_values(coq, haskell, racket)]
+        #[clap(add = ArgValueCompleter::new(catalog::complete_language))]
         language: String,
         /// Workload to be removed
-        /// [possible_values(bst, rbt, stlc, ifc)]
+        #[clap(add = ArgValueCompleter::new(catalog::complete_workload))]
         workload: String,
     },
+    #[clap(
+        name = "mv",
+        about = "Rename a workload of the experiment or move it to another language"
+    )]
+    Mv {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+        /// Workload to move, e.g. coq/bst
+        from: String,
+        /// New language and name of the workload, e.g. coq/bst2
+        to: String,
+    },
     #[clap(name = "list", about = "List all workloads")]
     ListWorkloads {
         /// Name of the experiment
@@ -140,9 +293,8 @@ enum WorkloadCommand {
         #[clap(short, long, default_value = None)]
         experiment: Option<String>,
         /// Language of the workload
-        /// [possible_values(coq, haskell, racket)]
         /// [default: all]
-        #[clap(short, long, default_value = "all")]
+        #[clap(short, long, default_value = "all", add = ArgValueCompleter::new(catalog::complete_language))]
         language: String,
         /// Available or experiment workloads
         /// [possible_values(available, experiment)]
@@ -150,6 +302,103 @@ enum WorkloadCommand {
         #[clap(short, long, default_value = "experiment")]
         kind: String,
     },
+    #[clap(
+        name = "check",
+        about = "Check the layout, properties and mutant markers of the workloads"
+    )]
+    Check {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+        /// Workload to check, e.g. coq/bst
+        /// [default: every workload of the experiment]
+        workload: Option<String>,
+        /// Also build the workloads whose toolchain is installed
+        #[clap(short, long)]
+        build: bool,
+    },
+    #[clap(
+        name = "new",
+        about = "Create a new workload in the experiment from a language template"
+    )]
+    New {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+        /// Language of the workload, one of coq, haskell or racket
+        language: String,
+        /// Name of the new workload, e.g. heap
+        name: String,
+    },
+    #[clap(
+        name = "status",
+        about = "Show how the workloads of the experiment differ from etna"
+    )]
+    Status {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+    },
+    #[clap(
+        name = "update",
+        about = "Merge the changes of a workload in etna into the experiment"
+    )]
+    Update {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+        /// Language of the workload
+        #[clap(add = ArgValueCompleter::new(catalog::complete_language))]
+        language: String,
+        /// Workload to be updated
+        #[clap(add = ArgValueCompleter::new(catalog::complete_workload))]
+        workload: String,
+    },
+    #[clap(
+        name = "variants",
+        about = "List the mutant variants of a workload and the properties they break"
+    )]
+    Variants {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+        /// Language of the workload
+        #[clap(add = ArgValueCompleter::new(catalog::complete_language))]
+        language: String,
+        /// Workload whose variants are listed
+        #[clap(add = ArgValueCompleter::new(catalog::complete_workload))]
+        workload: String,
+    },
+    #[clap(
+        name = "materialize",
+        about = "Copy a workload into a scratch directory with a single variant active"
+    )]
+    Materialize {
+        /// Name of the experiment
+        /// [default: current directory]
+        #[clap(short, long, default_value = None)]
+        experiment: Option<String>,
+        /// Language of the workload
+        #[clap(add = ArgValueCompleter::new(catalog::complete_language))]
+        language: String,
+        /// Workload to copy
+        #[clap(add = ArgValueCompleter::new(catalog::complete_workload))]
+        workload: String,
+        /// Variant to activate, see `etna workload variants`
+        variant: String,
+        /// Directory to copy the workload to
+        /// [default: etna-<language>-<workload>-<variant> in the temporary directory]
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Replace the directory if it exists
+        #[clap(long)]
+        overwrite: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -163,6 +412,18 @@ enum StoreCommand {
     },
     #[command(subcommand, name = "query", about = "Query the store")]
     Query(QueryOption),
+    #[clap(
+        name = "verify",
+        about = "Check the integrity of every reference in the store"
+    )]
+    Verify {
+        /// Repair the problems that can be fixed automatically
+        #[clap(short, long, default_value = "false")]
+        repair: bool,
+        /// Delete the metrics of unknown experiments while repairing, they cannot be recovered
+        #[clap(long, default_value = "false", requires = "repair")]
+        drop_dangling: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -11,6 +11,22 @@ pub(crate) fn invoke() -> anyhow::Result<()> {
         ("Path", etna_config.etna_dir.display().to_string()),
         ("Repository", etna_config.repo_dir.display().to_string()),
         ("Venv", etna_config.venv_dir.display().to_string()),
+        ("Save Patch", etna_config.save_etna_patch.to_string()),
+        (
+            "Git Name",
+            etna_config.git.name.unwrap_or("(git config)".to_string()),
+        ),
+        (
+            "Git Email",
+            etna_config.git.email.unwrap_or("(git config)".to_string()),
+        ),
+        (
+            "Commit Template",
+            etna_config
+                .git
+                .message_template
+                .unwrap_or("{message}".to_string()),
+        ),
     ];
 
     let mut table = Table::new(table);
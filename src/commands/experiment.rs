@@ -1,3 +1,5 @@
+pub(crate) mod experiment_history;
 pub(crate) mod new_experiment;
+pub(crate) mod plan_experiment;
 pub(crate) mod run_experiment;
 pub(crate) mod show_experiment;
\ No newline at end of file
@@ -0,0 +1,69 @@
+use anyhow::Context;
+use tabled::settings::{Extract, Style};
+
+use crate::{config::EtnaConfig, store::Store};
+
+/// Shows every snapshot of an experiment in chronological order, along with the
+/// components that changed since the previous snapshot and the number of metrics
+/// attached to each one.
+pub(crate) fn invoke(name: String) -> anyhow::Result<()> {
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    let store = Store::load(&etna_config.store_path()).context("Failed to load the store")?;
+
+    let history = store.get_experiment_history(&name);
+    if history.is_empty() {
+        anyhow::bail!("No experiment named '{}' found in the store", name);
+    }
+
+    let mut table = vec![(
+        "Time".to_string(),
+        "Experiment".to_string(),
+        "Etna".to_string(),
+        "Changes".to_string(),
+        "Metrics".to_string(),
+    )];
+
+    let mut previous = None;
+    for (experiment, time) in history {
+        let time = time
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let mut etna = experiment.snapshot.etna.chars().take(8).collect::<String>();
+        if experiment.snapshot.etna_worktree.is_some() {
+            etna.push_str(" (dirty)");
+        }
+
+        let changes = match previous {
+            None => "initial".to_string(),
+            Some(previous) => experiment.snapshot.changes(previous).join(", "),
+        };
+
+        let metrics = store
+            .metrics
+            .iter()
+            .filter(|metric| metric.experiment_id == experiment.id)
+            .count();
+
+        table.push((
+            time,
+            experiment.id.clone(),
+            etna,
+            changes,
+            metrics.to_string(),
+        ));
+
+        previous = Some(&experiment.snapshot);
+    }
+
+    let mut table = tabled::Table::new(table);
+
+    table
+        .with(Extract::segment(1.., ..))
+        .with(Style::modern_rounded());
+
+    println!("{}", table);
+
+    Ok(())
+}
@@ -44,7 +44,6 @@ use crate::{
 /// Query.py - A default script to query the collected data
 /// Analyze.py - A default script to analyze the collected data
 /// Visualize.py - A default script to visualize the collected data
-
 pub(crate) fn invoke(
     name: String,
     path: Option<std::path::PathBuf>,
@@ -120,28 +119,29 @@ pub(crate) fn invoke(
     let workloads_path = experiment_config.path.join("workloads");
     std::fs::create_dir(&workloads_path).context("Failed to create workloads directory")?;
 
+    let etna_config = EtnaConfig::get_etna_config()?;
+
     // Initialize a git repository
     git_driver::initialize_git_repo(
         &experiment_config.path,
         format!("Automated initialization commit for experiment '{}'", name).as_str(),
+        &etna_config.git,
     )?;
 
     // Update the etna store with the current experiment
-    let etna_config = EtnaConfig::get_etna_config()?;
-    let mut etna_store = Store::load(&etna_config.etna_dir.join("store.json"))
-        .context("Could not load the store")?;
-
-    let snapshot = etna_store.take_snapshot(&etna_config, &experiment_config)?;
-
-    etna_store.experiments.insert(Experiment {
-        name,
-        id: snapshot.experiment.clone(),
-        description: experiment_config.description,
-        path: experiment_config.path,
-        snapshot,
-    });
-
-    etna_store.save(&etna_config.etna_dir.join("store.json"))?;
+    Store::update(&etna_config.store_path(), |etna_store| {
+        let snapshot = etna_store.take_snapshot(&etna_config, &experiment_config)?;
+
+        etna_store.experiments.insert(Experiment {
+            name,
+            id: snapshot.experiment.clone(),
+            description: experiment_config.description.clone(),
+            path: experiment_config.path.clone(),
+            snapshot,
+        });
+
+        Ok(())
+    })?;
 
     Ok(())
 }
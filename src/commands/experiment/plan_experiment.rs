@@ -0,0 +1,63 @@
+use anyhow::Context;
+use tabled::settings::{Extract, Style};
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    run::{self, RunFilter},
+};
+
+/// Prints the tasks the run matrix of an experiment expands to, and the worst-case
+/// duration of running them, with every trial hitting its timeout.
+pub(crate) fn invoke(experiment_name: Option<String>, filter: RunFilter) -> anyhow::Result<()> {
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let tasks = run::plan(&etna_config, &experiment_config, &filter)?;
+
+    let mut table = vec![(
+        "Workload".to_string(),
+        "Variant".to_string(),
+        "Strategy".to_string(),
+        "Property".to_string(),
+        "Trials".to_string(),
+        "Timeout".to_string(),
+    )];
+
+    for task in tasks.iter() {
+        table.push((
+            format!("{}/{}", task.language, task.workload),
+            task.variant.clone(),
+            task.strategy.clone(),
+            task.property.clone(),
+            task.trials.to_string(),
+            format!("{}s", task.timeout),
+        ));
+    }
+
+    let mut table = tabled::Table::new(table);
+
+    table
+        .with(Extract::segment(1.., ..))
+        .with(Style::modern_rounded());
+
+    println!("{}", table);
+
+    let trials = tasks.iter().map(|task| task.trials).sum::<usize>();
+    let duration = run::worst_case_duration(&tasks) as u64;
+
+    println!(
+        "{} tasks, {} trials, worst-case duration {}h {:02}m {:02}s",
+        tasks.len(),
+        trials,
+        duration / 3600,
+        duration % 3600 / 60,
+        duration % 60
+    );
+
+    Ok(())
+}
@@ -1,11 +1,26 @@
+use std::{fs::File, process::Command};
+
 use anyhow::Context;
 use log::{info, warn};
 
 use crate::{
-    config::{EtnaConfig, ExperimentConfig}, git_driver, python_driver, store::Store
+    checkpoint::Checkpoint,
+    config::{EtnaConfig, ExperimentConfig},
+    git_driver,
+    run::{self, ExpectedBreaks, RunFilter, Task},
+    runner::{self, RunnerConfig},
+    scheduler::{self, Outcome},
+    store::{Metric, Run, Store},
 };
 
-pub(crate) fn invoke(experiment_name: Option<String>) -> anyhow::Result<()> {
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    filter: RunFilter,
+    jobs: usize,
+    resume: bool,
+    force: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
     let etna_config = EtnaConfig::get_etna_config()?;
 
     let experiment_config = experiment_name
@@ -14,10 +29,44 @@ pub(crate) fn invoke(experiment_name: Option<String>) -> anyhow::Result<()> {
         .or_else(|_| ExperimentConfig::from_current_dir())
         .context("No experiment name is provided, and the current directory is not an experiment directory")?;
 
-    let mut store = Store::load(&etna_config.store_path())?;
+    // A dry run leaves the store and the object archive untouched
+    let snapshot = if dry_run {
+        Store::preview_snapshot(&etna_config, &experiment_config)?
+    } else {
+        Store::update(&etna_config.store_path(), |store| {
+            store.take_snapshot(&etna_config, &experiment_config)
+        })?
+    };
+
+    let checkpoint_path = Checkpoint::path(&etna_config, &experiment_config.name);
+    let mut checkpoint = if resume {
+        let mut checkpoint = Checkpoint::load(&checkpoint_path)?.context(format!(
+            "No interrupted run of the experiment {} to resume",
+            experiment_config.name
+        ))?;
+
+        if checkpoint.snapshot != snapshot {
+            let changes = snapshot.changes(&checkpoint.snapshot).join(", ");
+            if !force {
+                anyhow::bail!(
+                    "The experiment {} changed since the interrupted run ({changes}), use --force to resume anyway",
+                    experiment_config.name
+                );
+            }
 
-    let snapshot = Store::take_snapshot(&mut store, &etna_config, &experiment_config)?;
+            warn!(
+                "Resuming the experiment {} even though it changed since the interrupted run ({changes})",
+                experiment_config.name
+            );
+            checkpoint.snapshot = snapshot.clone();
+        }
 
+        checkpoint
+    } else {
+        Checkpoint::new(snapshot.clone())
+    };
+
+    let store = Store::load(&etna_config.store_path())?;
     let experiment = store.get_experiment_by_name(&experiment_config.name)?;
 
     info!(
@@ -26,19 +75,234 @@ pub(crate) fn invoke(experiment_name: Option<String>) -> anyhow::Result<()> {
     );
 
     if snapshot != experiment.snapshot {
-        warn!(
-            "Updating snapshot for the experiment {}",
+        if dry_run {
+            warn!(
+                "The snapshot of the experiment {} would be updated",
+                experiment_config.name
+            );
+        } else {
+            warn!(
+                "Updating snapshot for the experiment {}",
+                experiment_config.name
+            );
+        }
+
+        git_driver::print_diff(&snapshot, &experiment.snapshot)?;
+
+        if !dry_run {
+            let experiment = experiment.with_snapshot(snapshot.clone());
+            Store::update(&etna_config.store_path(), |store| {
+                store.experiments.insert(experiment);
+                Ok(())
+            })?;
+        }
+    } else if dry_run {
+        info!(
+            "The snapshot of the experiment {} is up to date",
             experiment_config.name
         );
+    }
 
-        git_driver::print_diff(&snapshot, &experiment.snapshot)?;
+    // Querying benchtool runs python, a dry run keeps every variant and property pair instead
+    let tasks = if dry_run {
+        info!(
+            "Not querying benchtool in a dry run, every property is planned against every variant"
+        );
+        experiment_config
+            .matrix
+            .expand(&experiment_config, &ExpectedBreaks::new(), &filter)?
+    } else {
+        run::plan(&etna_config, &experiment_config, &filter)?
+    };
+    if tasks.is_empty() {
+        anyhow::bail!(
+            "No tasks to run for the experiment {}",
+            experiment_config.name
+        );
+    }
+
+    let (done, trials): (Vec<_>, Vec<_>) = tasks
+        .iter()
+        .flat_map(Task::trials)
+        .partition(|trial| checkpoint.is_done(trial));
+
+    if !done.is_empty() {
+        info!(
+            "Skipping {} trials finished by the interrupted run",
+            done.len()
+        );
+    }
+
+    let runner = runner::runner(&etna_config, &experiment_config, &snapshot.experiment)?;
+
+    if dry_run {
+        let commands = trials
+            .iter()
+            .map(|trial| runner.command(trial))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        print_commands(&commands);
+        return Ok(());
+    }
+
+    if trials.is_empty() {
+        info!(
+            "Every trial of the experiment {} has already run",
+            experiment_config.name
+        );
+        return Checkpoint::remove(&checkpoint_path);
+    }
+
+    checkpoint.save(&checkpoint_path)?;
+
+    let mut record = Run::new(&etna_config, &experiment_config.name, &snapshot)?;
+    Store::save_run(&etna_config.store_path(), &record)?;
+
+    if matches!(experiment_config.runner, RunnerConfig::Python)
+        && experiment_config.limits.cpu.is_some()
+    {
+        warn!("The python runner only reports trials stopped by the CPU limit if the collection script exits with 128 + the signal, others are recorded as failed or completed");
+    }
 
-        let experiment = experiment.with_snapshot(snapshot.clone());
-        store.experiments.insert(experiment);
-        store.save(&etna_config.store_path())?;
+    info!(
+        "Running {} trials of {} tasks for the experiment {} with {} jobs, logs are in '{}'",
+        trials.len(),
+        tasks.len(),
+        experiment_config.name,
+        jobs,
+        record.logs.display()
+    );
+
+    let result = scheduler::run(
+        trials,
+        jobs,
+        &experiment_config.limits,
+        runner.exclusive(),
+        |trial| {
+            let (mut command, scratch) = runner.prepare(trial)?;
+
+            let (stdout, stderr) = record.trial_logs(trial);
+            if let Some(dir) = stdout.parent() {
+                std::fs::create_dir_all(dir).context("Failed to create the trial log directory")?;
+            }
+            command
+                .stdout(File::create(&stdout).context("Failed to create the stdout log")?)
+                .stderr(File::create(&stderr).context("Failed to create the stderr log")?);
+
+            Ok((command, scratch))
+        },
+        |result| {
+            if result.outcome == Outcome::Failed {
+                warn!(
+                    "Trial {} of {} failed, see '{}'",
+                    result.trial.trial,
+                    result.trial.task.id(),
+                    record.trial_logs(&result.trial).1.display()
+                );
+            }
+
+            let mut data =
+                serde_json::to_value(result).context("Failed to serialize the result")?;
+            data["run"] = record.id.clone().into();
+
+            // The store is reloaded for every result, as trials may write to it as well
+            Store::append_metric(
+                &etna_config.store_path(),
+                Metric {
+                    data,
+                    experiment_id: snapshot.experiment.clone(),
+                },
+            )?;
+
+            checkpoint.record(result);
+            checkpoint.save(&checkpoint_path)
+        },
+    );
+
+    let succeeded = result.as_ref().is_ok_and(|summary| summary.failed == 0);
+    record.finish(if succeeded { 0 } else { 1 });
+    Store::save_run(&etna_config.store_path(), &record)?;
+
+    let summary = result?;
+
+    info!(
+        "Finished the experiment {}: {} completed, {} failed, {} timed out, {} exceeded a limit, {} skipped",
+        experiment_config.name,
+        summary.completed,
+        summary.failed,
+        summary.timed_out,
+        summary.limit_exceeded,
+        summary.skipped
+    );
+
+    if summary.failed > 0 {
+        anyhow::bail!(
+            "{} trials of the experiment {} failed, see the logs in '{}' and rerun with --resume to retry them",
+            summary.failed,
+            experiment_config.name,
+            record.logs.display()
+        );
+    }
+
+    Checkpoint::remove(&checkpoint_path)
+}
+
+/// Prints the commands a run would execute, the environment they share comes first
+fn print_commands(commands: &[Command]) {
+    let envs = |command: &Command| {
+        command
+            .get_envs()
+            .filter_map(|(key, value)| {
+                let value = value?;
+                Some((
+                    key.to_string_lossy().to_string(),
+                    value.to_string_lossy().to_string(),
+                ))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let shared = commands
+        .first()
+        .map(envs)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|var| commands.iter().all(|command| envs(command).contains(var)))
+        .collect::<Vec<_>>();
+
+    if let Some(dir) = commands.first().and_then(Command::get_current_dir) {
+        println!("Directory: {}", dir.display());
     }
 
-    python_driver::run_experiment(&etna_config, &experiment_config, snapshot)?;
+    println!("Environment:");
+    for (key, value) in shared.iter() {
+        println!("  {key}={}", quote(value));
+    }
 
-    Ok(())
+    println!("Commands ({}):", commands.len());
+    for command in commands {
+        let mut line = envs(command)
+            .into_iter()
+            .filter(|var| !shared.contains(var))
+            .map(|(key, value)| format!("{key}={}", quote(&value)))
+            .collect::<Vec<_>>();
+
+        line.push(quote(&command.get_program().to_string_lossy()));
+        line.extend(command.get_args().map(|arg| quote(&arg.to_string_lossy())));
+
+        println!("  {}", line.join(" "));
+    }
+}
+
+/// Quotes a value for a POSIX shell, when it needs quoting
+fn quote(value: &str) -> String {
+    let plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@+".contains(c));
+
+    if plain && !value.is_empty() {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
 }
@@ -1,2 +1,3 @@
+pub(crate) mod query;
+pub(crate) mod verify;
 pub(crate) mod write;
-pub(crate) mod query;
\ No newline at end of file
@@ -0,0 +1,305 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::info;
+use tabled::settings::{Extract, Style};
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    experiment::Experiment,
+    git_driver,
+    snapshot::{Snapshot, SnapshotType},
+    store::Store,
+};
+
+struct Issue {
+    subject: String,
+    problem: String,
+    status: Status,
+}
+
+#[derive(PartialEq)]
+enum Status {
+    Unresolved,
+    Repaired,
+    /// Reported, but not a reason for the verification to fail
+    Warning,
+}
+
+impl Issue {
+    fn new(subject: &str, problem: String) -> Self {
+        Self {
+            subject: subject.to_string(),
+            problem,
+            status: Status::Unresolved,
+        }
+    }
+
+    fn warning(subject: &str, problem: String) -> Self {
+        Self {
+            status: Status::Warning,
+            ..Self::new(subject, problem)
+        }
+    }
+}
+
+/// Checks the integrity of the store
+/// 1. Every snapshot referenced by an experiment exists in the store
+/// 2. Every experiment directory exists and holds a matching `config.toml`
+/// 3. Every tree referenced by an experiment exists in the experiment repository
+/// 4. Every snapshot in the store is recoverable from the object archive
+/// 5. Every metric refers to a known experiment
+///
+/// With `repair`, missing snapshots are reconstructed when their type can be inferred,
+/// and missing trees are copied between the experiment repositories and the archive.
+/// Metrics of unknown experiments cannot be recovered, with `drop_dangling` they are deleted.
+///
+/// Etna snapshots recorded before their working tree was tracked have no tree to check,
+/// they are reported as warnings.
+pub(crate) fn invoke(repair: bool, drop_dangling: bool) -> anyhow::Result<()> {
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    let _lock = Store::lock(&etna_config.store_path())?;
+    let mut store = Store::load(&etna_config.store_path()).context("Failed to load the store")?;
+    let archive_path = etna_config.objects_path();
+
+    let mut issues = vec![];
+    let mut modified = false;
+
+    let mut experiments = store.experiments.iter().cloned().collect::<Vec<_>>();
+    experiments.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+
+    for experiment in experiments.iter() {
+        let subject = format!("experiment {} ({})", experiment.name, experiment.id);
+
+        let has_repo = check_directory(experiment, &subject, &mut issues);
+
+        for (hash, snapshot) in references(experiment) {
+            if !store.snapshots.iter().any(|s| s.hash == hash) {
+                let mut issue =
+                    Issue::new(&subject, format!("snapshot '{hash}' is not in the store"));
+                if let (true, Some(snapshot)) = (repair, snapshot) {
+                    store.snapshots.insert(snapshot);
+                    modified = true;
+                    issue.status = Status::Repaired;
+                }
+                issues.push(issue);
+            }
+
+            // The etna snapshot refers to a commit of the etna repository, not to a tree
+            if !has_repo || hash == experiment.snapshot.etna {
+                continue;
+            }
+
+            if !git_driver::contains_tree(&experiment.path, &hash)? {
+                let mut issue = Issue::new(
+                    &subject,
+                    format!("tree '{hash}' is missing from the experiment repository"),
+                );
+                if repair && contains_tree(&archive_path, &hash)? {
+                    git_driver::copy_tree(&archive_path, &experiment.path, &hash)?;
+                    issue.status = Status::Repaired;
+                }
+                issues.push(issue);
+            }
+        }
+    }
+
+    let mut snapshots = store.snapshots.iter().collect::<Vec<_>>();
+    snapshots.sort_by_key(|s| s.typ.to_string());
+
+    for snapshot in snapshots {
+        let subject = format!("snapshot {} ({})", snapshot.typ, snapshot.hash);
+        let Some(tree) = snapshot.tree() else {
+            issues.push(Issue::warning(
+                &subject,
+                "recorded before etna trees were tracked, its tree cannot be checked".to_string(),
+            ));
+            continue;
+        };
+
+        if !contains_tree(&archive_path, tree)? {
+            let mut issue = Issue::new(&subject, format!("tree '{tree}' is not archived"));
+            if let (true, Some(repo_path)) = (repair, source_repo(snapshot)) {
+                if contains_tree(&repo_path, tree)? {
+                    git_driver::archive_tree(&repo_path, &archive_path, tree)?;
+                    issue.status = Status::Repaired;
+                }
+            }
+            issues.push(issue);
+        }
+    }
+
+    let experiment_ids = store
+        .experiments
+        .iter()
+        .map(|e| e.id.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut dangling_metrics = BTreeMap::<&str, usize>::new();
+    for metric in store.metrics.iter() {
+        if !experiment_ids.contains(metric.experiment_id.as_str()) {
+            *dangling_metrics.entry(&metric.experiment_id).or_default() += 1;
+        }
+    }
+
+    let dropped = repair && drop_dangling && !dangling_metrics.is_empty();
+
+    for (experiment_id, count) in dangling_metrics {
+        let mut issue = Issue::new(
+            &format!("metrics ({experiment_id})"),
+            format!("{count} metrics refer to an unknown experiment"),
+        );
+        if dropped {
+            issue.status = Status::Repaired;
+        } else {
+            issue.problem +=
+                ", they are not recoverable, use --repair --drop-dangling to delete them";
+        }
+        issues.push(issue);
+    }
+
+    if dropped {
+        store
+            .metrics
+            .retain(|m| experiment_ids.contains(m.experiment_id.as_str()));
+        modified = true;
+    }
+
+    if modified {
+        store
+            .save(&etna_config.store_path())
+            .context("Failed to save the store")?;
+    }
+
+    if issues.is_empty() {
+        info!(
+            "Verified {} experiments, {} snapshots and {} metrics",
+            store.experiments.len(),
+            store.snapshots.len(),
+            store.metrics.len()
+        );
+        return Ok(());
+    }
+
+    let mut table = vec![(
+        "Subject".to_string(),
+        "Problem".to_string(),
+        "Status".to_string(),
+    )];
+    table.extend(issues.iter().map(|issue| {
+        let status = match issue.status {
+            Status::Unresolved => "unresolved",
+            Status::Repaired => "repaired",
+            Status::Warning => "warning",
+        };
+        (
+            issue.subject.clone(),
+            issue.problem.clone(),
+            status.to_string(),
+        )
+    }));
+
+    let mut table = tabled::Table::new(table);
+
+    table
+        .with(Extract::segment(1.., ..))
+        .with(Style::modern_rounded());
+
+    println!("{}", table);
+
+    let remaining = issues
+        .iter()
+        .filter(|issue| issue.status == Status::Unresolved)
+        .count();
+    if remaining > 0 {
+        anyhow::bail!("Found {remaining} integrity problems in the store");
+    }
+
+    Ok(())
+}
+
+/// Reports a missing experiment directory or a mismatching `config.toml`,
+/// returns whether the directory holds a git repository to check the trees against.
+fn check_directory(experiment: &Experiment, subject: &str, issues: &mut Vec<Issue>) -> bool {
+    if !experiment.path.exists() {
+        issues.push(Issue::new(
+            subject,
+            format!("directory '{}' does not exist", experiment.path.display()),
+        ));
+        return false;
+    }
+
+    match ExperimentConfig::from_path(experiment.path.clone()) {
+        Ok(config) if config.name != experiment.name => issues.push(Issue::new(
+            subject,
+            format!(
+                "'{}' holds the configuration of experiment '{}'",
+                experiment.path.display(),
+                config.name
+            ),
+        )),
+        Ok(_) => {}
+        Err(e) => issues.push(Issue::new(subject, format!("{e:#}"))),
+    }
+
+    experiment.path.join(".git").exists()
+}
+
+/// Every snapshot hash referenced by an experiment, along with the snapshot that can be
+/// reconstructed for it if it goes missing from the store.
+fn references(experiment: &Experiment) -> Vec<(String, Option<Snapshot>)> {
+    let snapshot = &experiment.snapshot;
+
+    let mut references = vec![
+        (snapshot.experiment.clone(), None),
+        (snapshot.etna.clone(), None),
+    ];
+
+    references.extend(snapshot.scripts.iter().map(|(name, hash)| {
+        let snapshot = Snapshot {
+            path: experiment.path.join(name),
+            typ: SnapshotType::Script { name: name.clone() },
+            hash: hash.clone(),
+        };
+        (hash.clone(), Some(snapshot))
+    }));
+
+    references.extend(snapshot.workloads.iter().map(|(workload, hash)| {
+        let snapshot = Snapshot {
+            path: experiment
+                .path
+                .join("workloads")
+                .join(&workload.language)
+                .join(&workload.name)
+                .join("*"),
+            typ: SnapshotType::Workload {
+                name: workload.name.clone(),
+                language: workload.language.clone(),
+            },
+            hash: hash.clone(),
+        };
+        (hash.clone(), Some(snapshot))
+    }));
+
+    references
+}
+
+/// The repository a snapshot was taken from, found by walking up its path
+fn source_repo(snapshot: &Snapshot) -> Option<PathBuf> {
+    snapshot
+        .path
+        .ancestors()
+        .find(|path| path.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+fn contains_tree(repo_path: &Path, tree: &str) -> anyhow::Result<bool> {
+    if !repo_path.exists() {
+        return Ok(false);
+    }
+
+    git_driver::contains_tree(repo_path, tree)
+}
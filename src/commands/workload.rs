@@ -1,3 +1,10 @@
 pub(crate) mod add_workload;
+pub(crate) mod check_workload;
 pub(crate) mod list_workloads;
+pub(crate) mod materialize_variant;
+pub(crate) mod mv_workload;
+pub(crate) mod new_workload;
 pub(crate) mod remove_workload;
+pub(crate) mod status_workload;
+pub(crate) mod update_workload;
+pub(crate) mod variants_workload;
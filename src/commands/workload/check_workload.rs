@@ -0,0 +1,356 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use tabled::settings::{Extract, Style};
+
+use crate::{
+    catalog,
+    config::{EtnaConfig, ExperimentConfig},
+    scheduler,
+    workload::{self, Workload},
+};
+
+/// Number of output lines kept in the details of a failed build
+const BUILD_OUTPUT_LINES: usize = 5;
+
+/// Result of a single check of a workload
+struct Check {
+    name: &'static str,
+    status: Status,
+    details: String,
+}
+
+#[derive(PartialEq)]
+enum Status {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Fail => "fail",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+impl Check {
+    fn new(name: &'static str, failures: Vec<String>, success: String) -> Self {
+        if failures.is_empty() {
+            Self {
+                name,
+                status: Status::Pass,
+                details: success,
+            }
+        } else {
+            Self {
+                name,
+                status: Status::Fail,
+                details: failures.join("\n"),
+            }
+        }
+    }
+}
+
+/// Validates the layout, the properties, strategies and mutant markers of the workloads
+/// of the experiment, and optionally builds them, printing a table of checks per workload
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    workload: Option<String>,
+    build: bool,
+) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let names = experiment_config
+        .workloads
+        .iter()
+        .map(|w| format!("{}/{}", w.language, w.name))
+        .collect::<Vec<_>>();
+
+    let workloads = match workload {
+        None => experiment_config.workloads.iter().collect::<Vec<_>>(),
+        Some(workload) => {
+            let Some(index) = names.iter().position(|n| n.eq_ignore_ascii_case(&workload)) else {
+                let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+                anyhow::bail!(
+                    "Workload '{workload}' does not exist{}",
+                    catalog::hint(&workload, &names)
+                );
+            };
+            vec![&experiment_config.workloads[index]]
+        }
+    };
+
+    let mut failed = 0;
+
+    for workload in workloads.iter() {
+        let checks = check(&experiment_config.path, workload, build)?;
+
+        if checks.iter().any(|c| c.status == Status::Fail) {
+            failed += 1;
+        }
+
+        let mut table = vec![["Check", "Result", "Details"].map(String::from)];
+        table.extend(
+            checks
+                .into_iter()
+                .map(|c| [c.name.to_string(), c.status.as_str().to_string(), c.details]),
+        );
+
+        let mut table = tabled::Table::new(table);
+        table
+            .with(Extract::segment(1.., ..))
+            .with(Style::modern_rounded());
+
+        println!("{}/{}", workload.language, workload.name);
+        println!("{}", table);
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "{failed} of {} workloads failed the checks",
+            workloads.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs every check on a workload of the experiment
+fn check(root: &Path, workload: &Workload, build: bool) -> anyhow::Result<Vec<Check>> {
+    let path = workload.path(root);
+
+    if !path.is_dir() {
+        return Ok(vec![Check {
+            name: "Layout",
+            status: Status::Fail,
+            details: format!("'{}' does not exist", path.display()),
+        }]);
+    }
+
+    let files = workload::source_files(&path)?;
+    let relative = |file: &Path| file.strip_prefix(&path).unwrap_or(file).to_path_buf();
+
+    let strategies = files
+        .iter()
+        .filter(|file| workload::is_strategy(&relative(file)))
+        .collect::<Vec<_>>();
+    let specs = files
+        .iter()
+        .filter(|file| {
+            file.file_stem()
+                .is_some_and(|stem| stem.eq_ignore_ascii_case("spec"))
+        })
+        .collect::<Vec<_>>();
+
+    let mut checks = vec![check_layout(workload, &files, &specs, &strategies)];
+
+    // Properties of the specs, and whether every strategy tests each of them
+    let mut properties = vec![];
+    for spec in specs.iter() {
+        properties.extend(workload::properties(&read(spec)?));
+    }
+    properties.sort();
+    properties.dedup();
+
+    let mut missing = vec![];
+    for strategy in strategies.iter() {
+        // Whole identifiers, so that `prop_InsertValid` does not cover `prop_Insert`
+        let tested = workload::properties(&read(strategy)?);
+        let untested = properties
+            .iter()
+            .filter(|p| !tested.contains(p))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !untested.is_empty() {
+            missing.push(format!(
+                "{} does not test {}",
+                relative(strategy).display(),
+                untested.join(", ")
+            ));
+        }
+    }
+    if properties.is_empty() {
+        missing.push("No 'prop_' definitions in the Spec files".to_string());
+    }
+
+    checks.push(Check::new(
+        "Properties",
+        missing,
+        format!(
+            "{} properties tested by {} strategies",
+            properties.len(),
+            strategies.len()
+        ),
+    ));
+
+    // Mutant markers of the sources, strategies are not mutated
+    let mut errors = vec![];
+    let mut variants = 0;
+    for file in workload::marked_files(&path)? {
+        variants += file
+            .mutations
+            .iter()
+            .map(|m| m.variants.len())
+            .sum::<usize>();
+        errors.extend(
+            file.errors
+                .iter()
+                .map(|e| format!("{}:{}: {}", file.path.display(), e.line, e.message)),
+        );
+    }
+
+    checks.push(Check::new(
+        "Markers",
+        errors,
+        format!("{variants} variants"),
+    ));
+
+    checks.push(if build {
+        check_build(workload, &path)?
+    } else {
+        Check {
+            name: "Build",
+            status: Status::Skipped,
+            details: "Run with --build to build the workload".to_string(),
+        }
+    });
+
+    Ok(checks)
+}
+
+fn check_layout(
+    workload: &Workload,
+    files: &[PathBuf],
+    specs: &[&PathBuf],
+    strategies: &[&PathBuf],
+) -> Check {
+    let mut failures = vec![];
+
+    if let Some(extension) = workload::extension(&workload.language) {
+        if !files
+            .iter()
+            .any(|f| f.extension().is_some_and(|e| e == extension))
+        {
+            failures.push(format!("No {} sources", workload.language));
+        }
+    }
+    if specs.is_empty() {
+        failures.push("No Spec file".to_string());
+    }
+    if strategies.is_empty() {
+        failures.push("No strategies, expected them in a 'Strategies' directory".to_string());
+    }
+
+    Check::new("Layout", failures, format!("{} source files", files.len()))
+}
+
+/// Builds a copy of the workload, so that the build artifacts stay out of the experiment
+fn check_build(workload: &Workload, path: &Path) -> anyhow::Result<Check> {
+    let skipped = |details: String| Check {
+        name: "Build",
+        status: Status::Skipped,
+        details,
+    };
+
+    let Some((toolchain, script)) = build_script(&workload.language, path) else {
+        return Ok(skipped(format!(
+            "No known build for {} workloads",
+            workload.language
+        )));
+    };
+
+    if !on_path(toolchain) {
+        return Ok(skipped(format!("'{toolchain}' is not installed")));
+    }
+
+    // Removed when dropped, whether the build succeeds or not
+    let scratch =
+        scheduler::private_dir("etna-check-").context("Failed to create the build directory")?;
+    let dir = scratch.path().join(&workload.name);
+
+    workload::copy_dir(path, &dir).context("Failed to copy the workload to build it")?;
+
+    let output = Command::new("sh")
+        .args(["-c", &script])
+        .current_dir(&dir)
+        .output()
+        .context(format!("Failed to run '{script}'"))?;
+
+    if output.status.success() {
+        return Ok(Check {
+            name: "Build",
+            status: Status::Pass,
+            details: script,
+        });
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let log = if stderr.trim().is_empty() {
+        stdout
+    } else {
+        stderr
+    };
+    let lines = log.trim_end().lines().collect::<Vec<_>>();
+
+    Ok(Check {
+        name: "Build",
+        status: Status::Fail,
+        details: format!(
+            "'{script}' failed with {}\n{}",
+            output.status,
+            lines[lines.len().saturating_sub(BUILD_OUTPUT_LINES)..].join("\n")
+        ),
+    })
+}
+
+/// The toolchain and the shell script building a workload of a language, from the
+/// workload directory
+fn build_script(language: &str, path: &Path) -> Option<(&'static str, String)> {
+    let script = match language.to_lowercase().as_str() {
+        "coq" if path.join("Makefile").exists() => ("coqc", "make".to_string()),
+        "coq" if path.join("_CoqProject").exists() => (
+            "coqc",
+            "coq_makefile -f _CoqProject -o Makefile.coq && make -f Makefile.coq".to_string(),
+        ),
+        "haskell" if path.join("stack.yaml").exists() => ("stack", "stack build".to_string()),
+        "haskell" => ("cabal", "cabal build".to_string()),
+        "racket" => {
+            let files = workload::source_files(path).ok()?;
+            let files = files
+                .iter()
+                .filter_map(|f| f.strip_prefix(path).ok())
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>();
+            ("raco", format!("raco make {}", files.join(" ")))
+        }
+        _ => return None,
+    };
+
+    Some(script)
+}
+
+/// Whether a program can be found in `PATH`
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+fn read(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).context(format!("Failed to read '{}'", path.display()))
+}
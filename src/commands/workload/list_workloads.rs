@@ -1,9 +1,11 @@
 use anyhow::Context;
+use log::warn;
 use tabled::settings::{Extract, Style};
 
 use crate::{
+    catalog::Catalog,
     config::{EtnaConfig, ExperimentConfig},
-    workload::Workload,
+    workload::{self, Workload},
 };
 
 pub(crate) fn invoke(
@@ -20,19 +22,48 @@ pub(crate) fn invoke(
         .or_else(|_| ExperimentConfig::from_current_dir())
         .context("No experiment name is provided, and the current directory is not an experiment directory")?;
 
+    // Languages only known to the experiment, e.g. of imported workloads, are listed too
+    let language = match language.as_str() {
+        "all" => language,
+        language => Catalog::load(&etna_config)
+            .unwrap_or_default()
+            .known_language(&experiment_config, language)?,
+    };
+
     match kind.as_str() {
         "experiment" => {
             let mut languages = experiment_config
                 .workloads
                 .iter()
-                .filter(|workload| language == "all" || language == workload.language)
+                .filter(|workload| selected(&language, workload))
                 .collect::<Vec<&Workload>>();
 
             languages.sort_by(|a, b| a.language.cmp(&b.language).then(a.name.cmp(&b.name)));
 
-            let mut table = vec![("Language", "Name")];
+            let mut table =
+                vec![["Language", "Name", "Source", "Commit", "Tree"].map(String::from)];
             for workload in languages {
-                table.push((workload.language.as_str(), workload.name.as_str()));
+                let (repo, commit, tree) = match &workload.source {
+                    Some(source) => (
+                        source.repo.clone(),
+                        source
+                            .commit
+                            .as_deref()
+                            .map(short)
+                            .unwrap_or("-")
+                            .to_string(),
+                        short(&source.tree).to_string(),
+                    ),
+                    None => ("unknown".to_string(), "-".to_string(), "-".to_string()),
+                };
+
+                table.push([
+                    workload.language.clone(),
+                    workload.name.clone(),
+                    repo,
+                    commit,
+                    tree,
+                ]);
             }
 
             let mut table = tabled::Table::new(table);
@@ -44,7 +75,59 @@ pub(crate) fn invoke(
             println!("{}", table);
         }
         "available" => {
-            anyhow::bail!("'available' kind is not implemented yet");
+            let repo_dir = etna_config.workloads_repo_dir();
+            let workloads = workload::available(&repo_dir).context(format!(
+                "Failed to list the workloads of '{}'",
+                repo_dir.display()
+            ))?;
+
+            let mut table = vec![[
+                "Language",
+                "Name",
+                "Variants",
+                "Strategies",
+                "Properties",
+                "In Experiment",
+            ]
+            .map(String::from)];
+
+            for workload in workloads.iter().filter(|w| selected(&language, w)) {
+                let counts = match workload::inspect(&workload.path(&repo_dir)) {
+                    Ok(contents) => [
+                        contents.variants.len(),
+                        contents.strategies.len(),
+                        contents.properties.len(),
+                    ]
+                    .map(|count| count.to_string()),
+                    Err(e) => {
+                        warn!(
+                            "Failed to inspect workload '{}/{}': {e:#}",
+                            workload.language, workload.name
+                        );
+                        ["?", "?", "?"].map(String::from)
+                    }
+                };
+                let [variants, strategies, properties] = counts;
+
+                let added = experiment_config.has_workload(&workload.language, &workload.name);
+
+                table.push([
+                    workload.language.clone(),
+                    workload.name.clone(),
+                    variants,
+                    strategies,
+                    properties,
+                    if added { "✓" } else { "" }.to_string(),
+                ]);
+            }
+
+            let mut table = tabled::Table::new(table);
+
+            table
+                .with(Extract::segment(1.., ..))
+                .with(Style::modern_rounded());
+
+            println!("{}", table);
         }
         _ => {
             anyhow::bail!("Invalid kind: {}", kind);
@@ -53,3 +136,12 @@ pub(crate) fn invoke(
 
     Ok(())
 }
+
+/// Whether a workload is in the language selected on the command line
+fn selected(language: &str, workload: &Workload) -> bool {
+    language == "all" || language.eq_ignore_ascii_case(&workload.language)
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..8.min(hash.len())]
+}
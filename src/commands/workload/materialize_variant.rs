@@ -0,0 +1,62 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use log::info;
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    workload,
+};
+
+/// Copies a workload into a scratch directory with a single mutant variant active,
+/// to debug the variant outside of a run
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    language: String,
+    workload: String,
+    variant: String,
+    output: Option<PathBuf>,
+    overwrite: bool,
+) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let workload = experiment_config.find_workload(&language, &workload)?;
+    let workload_path = workload.path(&experiment_config.path);
+
+    let output = output.unwrap_or_else(|| {
+        std::env::temp_dir().join(format!(
+            "etna-{}-{}-{variant}",
+            workload.language, workload.name
+        ))
+    });
+
+    if output.exists() {
+        if !overwrite {
+            anyhow::bail!(
+                "'{}' already exists, use --overwrite to replace it",
+                output.display()
+            );
+        }
+        fs::remove_dir_all(&output).context(format!("Failed to remove '{}'", output.display()))?;
+    }
+
+    let applied = workload::materialize(&workload_path, &output, &variant).context(format!(
+        "Failed to materialize '{variant}' of '{}/{}'",
+        workload.language, workload.name
+    ))?;
+
+    for path in applied {
+        info!("Applied '{variant}' to '{}'", path.display());
+    }
+
+    println!("{}", output.display());
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use anyhow::Context;
+use log::info;
+
+use crate::{
+    catalog::Catalog,
+    config::{EtnaConfig, ExperimentConfig},
+    transaction::Transaction,
+};
+
+/// Renames a workload of the experiment, or moves it to another language
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    from: String,
+    to: String,
+) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let (language, name) = split(&from)?;
+    let (new_language, new_name) = split(&to)?;
+
+    // Spell the language as the experiment or etna already does, e.g. `coq` as `Coq`
+    let new_language = Catalog::load(&etna_config)
+        .unwrap_or_default()
+        .known_language(&experiment_config, new_language)?;
+
+    let mut transaction = Transaction::begin(&etna_config, experiment_config)?;
+    transaction.rename(language, name, &new_language, new_name)?;
+    transaction.commit()?;
+
+    info!("Moved workload '{from}' to '{new_language}/{new_name}'");
+
+    Ok(())
+}
+
+/// Splits a `<language>/<name>` argument
+fn split(workload: &str) -> anyhow::Result<(&str, &str)> {
+    match workload.split_once('/') {
+        Some((language, name))
+            if !language.is_empty() && !name.is_empty() && !name.contains('/') =>
+        {
+            Ok((language, name))
+        }
+        _ => anyhow::bail!("Invalid workload '{workload}', expected '<language>/<name>'"),
+    }
+}
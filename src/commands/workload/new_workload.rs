@@ -0,0 +1,147 @@
+use anyhow::Context;
+use log::info;
+
+use crate::{
+    catalog,
+    config::{EtnaConfig, ExperimentConfig},
+    transaction::Transaction,
+    workload::{self, Workload, LANGUAGES},
+};
+
+/// Placeholder for the name of the workload in the templates
+const NAME_PLACEHOLDER: &str = "{{name}}";
+
+/// Files of a new Coq workload, as paths relative to the workload and their templates
+const COQ_TEMPLATES: [(&str, &str); 5] = [
+    (
+        "Src/Impl.v",
+        std::include_str!("../../../templates/workloads/Coq/Src/Impl.vt"),
+    ),
+    (
+        "Src/Spec.v",
+        std::include_str!("../../../templates/workloads/Coq/Src/Spec.vt"),
+    ),
+    (
+        "Strategies/BespokeGenerator.v",
+        std::include_str!("../../../templates/workloads/Coq/Strategies/BespokeGenerator.vt"),
+    ),
+    (
+        "_CoqProject",
+        std::include_str!("../../../templates/workloads/Coq/_CoqProjectt"),
+    ),
+    (
+        "Makefile",
+        std::include_str!("../../../templates/workloads/Coq/Makefilet"),
+    ),
+];
+
+/// Files of a new Haskell workload
+const HASKELL_TEMPLATES: [(&str, &str); 4] = [
+    (
+        "Src/Impl.hs",
+        std::include_str!("../../../templates/workloads/Haskell/Src/Impl.hst"),
+    ),
+    (
+        "Src/Spec.hs",
+        std::include_str!("../../../templates/workloads/Haskell/Src/Spec.hst"),
+    ),
+    (
+        "Strategies/Quick.hs",
+        std::include_str!("../../../templates/workloads/Haskell/Strategies/Quick.hst"),
+    ),
+    (
+        "{{name}}.cabal",
+        std::include_str!("../../../templates/workloads/Haskell/workload.cabalt"),
+    ),
+];
+
+/// Files of a new Racket workload
+const RACKET_TEMPLATES: [(&str, &str); 4] = [
+    (
+        "Src/impl.rkt",
+        std::include_str!("../../../templates/workloads/Racket/Src/impl.rktt"),
+    ),
+    (
+        "Src/spec.rkt",
+        std::include_str!("../../../templates/workloads/Racket/Src/spec.rktt"),
+    ),
+    (
+        "Strategies/bespoke.rkt",
+        std::include_str!("../../../templates/workloads/Racket/Strategies/bespoke.rktt"),
+    ),
+    (
+        "info.rkt",
+        std::include_str!("../../../templates/workloads/Racket/info.rktt"),
+    ),
+];
+
+/// Creates a workload in the experiment from the template of its language, with a sample
+/// property, strategy and mutant variant, and registers it like an added workload.
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    language: String,
+    name: String,
+) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let Some(language) = LANGUAGES.iter().find(|l| l.eq_ignore_ascii_case(&language)) else {
+        anyhow::bail!(
+            "There is no template for language '{language}'{}",
+            catalog::hint(&language, &LANGUAGES)
+        );
+    };
+
+    let templates: &[(&str, &str)] = match *language {
+        "Coq" => &COQ_TEMPLATES,
+        "Haskell" => &HASKELL_TEMPLATES,
+        _ => &RACKET_TEMPLATES,
+    };
+
+    workload::check_name(language, &name)?;
+
+    // Check if the workload already exists
+    if experiment_config.has_workload(language, &name) {
+        anyhow::bail!("Workload '{}/{}' already exists", language, name);
+    }
+
+    let workload = Workload {
+        language: language.to_string(),
+        name: name.clone(),
+        source: None,
+    };
+
+    let workload_path = workload.path(&experiment_config.path);
+
+    // Create the workload files
+    let mut transaction = Transaction::begin(&etna_config, experiment_config)?;
+    transaction.add(workload, |dest| {
+        for (path, content) in templates {
+            let path = dest.join(path.replace(NAME_PLACEHOLDER, &name));
+
+            std::fs::create_dir_all(path.parent().context("Failed to get parent directory")?)
+                .context("Failed to create workload directory")?;
+            std::fs::write(&path, content.replace(NAME_PLACEHOLDER, &name)).context(format!(
+                "Failed to create template file '{}'",
+                path.display()
+            ))?;
+        }
+
+        Ok(())
+    })?;
+
+    transaction.commit()?;
+
+    info!(
+        "Created workload '{language}/{name}' in '{}'",
+        workload_path.display()
+    );
+
+    Ok(())
+}
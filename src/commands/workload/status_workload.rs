@@ -0,0 +1,62 @@
+use anyhow::Context;
+use tabled::settings::{Extract, Style};
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    store::Store,
+    workload,
+};
+
+/// Compares each workload of the experiment with its copy in etna, and with the copy
+/// it was added or last updated from
+pub(crate) fn invoke(experiment_name: Option<String>) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let store = Store::load(&etna_config.store_path()).context("Failed to load the store")?;
+
+    let short = |hash: &Option<String>| {
+        hash.as_ref()
+            .map(|hash| hash[..8.min(hash.len())].to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    let mut table = vec![["Workload", "Local", "Etna", "Base", "Status"].map(String::from)];
+
+    for workload in experiment_config.workloads.iter() {
+        let name = format!("{}/{}", workload.language, workload.name);
+
+        match workload::drift(&etna_config, &experiment_config, &store, workload) {
+            Ok(drift) => table.push([
+                name,
+                short(&Some(drift.local.clone())),
+                short(&drift.upstream),
+                short(&drift.base),
+                drift.status().to_string(),
+            ]),
+            Err(e) => table.push([
+                name,
+                "-".to_string(),
+                "-".to_string(),
+                "-".to_string(),
+                format!("{e:#}"),
+            ]),
+        }
+    }
+
+    let mut table = tabled::Table::new(table);
+
+    table
+        .with(Extract::segment(1.., ..))
+        .with(Style::modern_rounded());
+
+    println!("{}", table);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use anyhow::Context;
+use log::info;
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    git_driver,
+    store::Store,
+    transaction::Transaction,
+    workload,
+};
+
+/// Pulls the changes of the etna copy of a workload into the experiment, with a three-way
+/// merge against the copy it was added or last updated from, keeping local edits.
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    language: String,
+    workload: String,
+) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let store = Store::load(&etna_config.store_path()).context("Failed to load the store")?;
+
+    let current = experiment_config
+        .find_workload(&language, &workload)?
+        .clone();
+    let name = format!("{}/{}", current.language, current.name);
+
+    let drift = workload::drift(&etna_config, &experiment_config, &store, &current)?;
+
+    let upstream = drift
+        .upstream
+        .clone()
+        .context(format!("Workload '{name}' no longer exists in etna"))?;
+    let base = drift.base.clone().context(format!(
+        "The etna copy '{name}' was taken from is unknown, it cannot be merged"
+    ))?;
+
+    if upstream == base {
+        info!("Workload '{name}' is {}", drift.status());
+        return Ok(());
+    }
+
+    let merged = if drift.local == base {
+        upstream.clone()
+    } else {
+        match git_driver::merge_trees(&experiment_config.path, &base, &drift.local, &upstream)? {
+            git_driver::Merge::Merged(tree) => tree,
+            git_driver::Merge::Conflicts(paths) => anyhow::bail!(
+                "Updating '{name}' conflicts with local changes in:\n  {}",
+                paths.join("\n  ")
+            ),
+        }
+    };
+
+    // The etna copy is the base of the next update
+    let source = workload::source(&etna_config, &etna_config.workloads_repo_dir(), &current)?;
+
+    let mut transaction = Transaction::begin(&etna_config, experiment_config)?;
+    let entry = transaction.update(&current.language, &current.name, &drift.local, &merged)?;
+    entry.source = Some(source);
+    transaction.commit()?;
+
+    info!("Updated workload '{name}'");
+
+    Ok(())
+}
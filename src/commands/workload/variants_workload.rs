@@ -0,0 +1,75 @@
+use anyhow::Context;
+use log::warn;
+use tabled::settings::{Extract, Style};
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    python_driver, workload,
+};
+
+/// Lists the mutant variants of a workload, where they are and the properties
+/// benchtool expects them to break
+pub(crate) fn invoke(
+    experiment_name: Option<String>,
+    language: String,
+    workload: String,
+) -> anyhow::Result<()> {
+    // Get etna configuration
+    let etna_config = EtnaConfig::get_etna_config().context("Failed to get etna config")?;
+    // Get the current experiment
+    let experiment_config = experiment_name
+        .ok_or(anyhow::anyhow!("No experiment name provided"))
+        .and_then(|n| ExperimentConfig::from_etna_config(&n, &etna_config))
+        .or_else(|_| ExperimentConfig::from_current_dir())
+        .context("No experiment name is provided, and the current directory is not an experiment directory")?;
+
+    let workload = experiment_config.find_workload(&language, &workload)?;
+    let files = workload::marked_files(&workload.path(&experiment_config.path))?;
+
+    for file in files.iter() {
+        for error in file.errors.iter() {
+            warn!("{}:{}: {}", file.path.display(), error.line, error.message);
+        }
+    }
+
+    let expected_breaks = python_driver::expected_breaks(&etna_config).unwrap_or_else(|e| {
+        warn!("Failed to get the expected breaks from benchtool: {e:#}");
+        Default::default()
+    });
+    let breaks = expected_breaks
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(&workload.name))
+        .map(|(_, breaks)| breaks);
+
+    let mut table = vec![["Variant", "Location", "Breaks"].map(String::from)];
+
+    for file in files.iter() {
+        for mutation in file.mutations.iter() {
+            for variant in mutation.variants.iter() {
+                let expected = match breaks {
+                    Some(breaks) => breaks
+                        .get(&variant.name)
+                        .map(|properties| properties.join(", "))
+                        .unwrap_or_else(|| "-".to_string()),
+                    None => "unknown".to_string(),
+                };
+
+                table.push([
+                    variant.name.clone(),
+                    format!("{}:{}", file.path.display(), variant.line),
+                    expected,
+                ]);
+            }
+        }
+    }
+
+    let mut table = tabled::Table::new(table);
+
+    table
+        .with(Extract::segment(1.., ..))
+        .with(Style::modern_rounded());
+
+    println!("{}", table);
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
-use crate::{store::Store, workload::Workload};
+use crate::{
+    catalog, run::RunMatrix, runner::RunnerConfig, scheduler::Limits, store::Store,
+    workload::Workload,
+};
 use anyhow::Context;
 use serde_derive::{Deserialize, Serialize};
 
@@ -12,6 +15,19 @@ pub(crate) struct ExperimentConfig {
     pub name: String,
     pub description: String,
     pub workloads: Vec<Workload>,
+    /// Collection script run for every trial, relative to the experiment directory
+    #[serde(default = "ExperimentConfig::default_script")]
+    pub script: PathBuf,
+    #[serde(default)]
+    pub runner: RunnerConfig,
+    #[serde(default)]
+    pub matrix: RunMatrix,
+    #[serde(default)]
+    pub limits: Limits,
+    /// Environment variables set for every process of the experiment, where `${NAME}`
+    /// stands for the variable as etna sets it, e.g. `PATH = "/opt/coq/bin:${PATH}"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
     #[serde(skip)]
     #[serde(default)]
     pub path: PathBuf,
@@ -23,10 +39,24 @@ impl ExperimentConfig {
             name: name.to_string(),
             description: description.to_string(),
             workloads: vec![],
+            script: Self::default_script(),
+            runner: RunnerConfig::default(),
+            matrix: RunMatrix::default(),
+            limits: Limits::default(),
+            env: BTreeMap::new(),
             path,
         }
     }
 
+    fn default_script() -> PathBuf {
+        PathBuf::from("Collect.py")
+    }
+
+    /// Name of the collection script, as recorded in the experiment snapshots
+    pub(crate) fn script_name(&self) -> String {
+        self.script.display().to_string()
+    }
+
     pub(crate) fn from_path(path: PathBuf) -> anyhow::Result<Self> {
         // Check for the config file
         let config_path = path.join("config.toml");
@@ -65,6 +95,27 @@ impl ExperimentConfig {
             .iter()
             .any(|w| w.language == language && w.name == name)
     }
+
+    /// A workload of the experiment, ignoring case, with a hint when there is none
+    pub(crate) fn find_workload(&self, language: &str, name: &str) -> anyhow::Result<&Workload> {
+        let found = self.workloads.iter().find(|w| {
+            w.language.eq_ignore_ascii_case(language) && w.name.eq_ignore_ascii_case(name)
+        });
+
+        found.with_context(|| {
+            let names = self
+                .workloads
+                .iter()
+                .map(|w| format!("{}/{}", w.language, w.name))
+                .collect::<Vec<_>>();
+            let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+
+            format!(
+                "Workload '{language}/{name}' does not exist{}",
+                catalog::hint(&format!("{language}/{name}"), &names)
+            )
+        })
+    }
 }
 
 /// Etna Configuration
@@ -76,6 +127,22 @@ pub(crate) struct EtnaConfig {
     pub venv_dir: PathBuf,
     pub branch: String,
     pub configured: bool,
+    /// Save the local changes of the etna repository as a patch when taking a snapshot
+    #[serde(default)]
+    pub save_etna_patch: bool,
+    /// Identity and message template for the automated commits
+    #[serde(default)]
+    pub git: GitConfig,
+}
+
+/// Git Configuration
+/// The identity defaults to `user.name` and `user.email` from the git configuration.
+/// In the message template, `{message}` is replaced by the description of the change.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct GitConfig {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub message_template: Option<String>,
 }
 
 impl EtnaConfig {
@@ -92,6 +159,8 @@ impl EtnaConfig {
             venv_dir,
             branch,
             configured,
+            save_etna_patch: false,
+            git: GitConfig::default(),
         })
     }
 
@@ -125,4 +194,27 @@ impl EtnaConfig {
     pub(crate) fn store_path(&self) -> PathBuf {
         self.etna_dir.join("store.json")
     }
+
+    pub(crate) fn patches_path(&self) -> PathBuf {
+        self.etna_dir.join("patches")
+    }
+
+    pub(crate) fn objects_path(&self) -> PathBuf {
+        self.etna_dir.join("objects")
+    }
+
+    /// The etna repository workloads are taken from, `ETNA_REPO_DIR` if it is set
+    pub(crate) fn workloads_repo_dir(&self) -> PathBuf {
+        std::env::var("ETNA_REPO_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| self.repo_dir.clone())
+    }
+
+    pub(crate) fn runs_path(&self) -> PathBuf {
+        self.etna_dir.join("runs")
+    }
+
+    pub(crate) fn checkpoints_path(&self) -> PathBuf {
+        self.etna_dir.join("checkpoints")
+    }
 }
@@ -0,0 +1,258 @@
+use std::{collections::BTreeMap, process::Command};
+
+use anyhow::Context;
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    scheduler::Trial,
+};
+
+/// Environment of a process spawned by etna.
+/// It is set on each `Command` rather than on the current process, so that several
+/// experiments can run side by side from the same process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ExecutionEnv {
+    pub vars: BTreeMap<String, String>,
+}
+
+impl ExecutionEnv {
+    /// Environment activating the etna virtual environment
+    pub(crate) fn venv(etna_config: &EtnaConfig) -> anyhow::Result<Self> {
+        let path = format!(
+            "{}/bin:{}",
+            etna_config.venv_dir.display(),
+            std::env::var("PATH").context("PATH is not present in the environment")?
+        );
+
+        Ok(Self::default()
+            .with("VIRTUAL_ENV", etna_config.venv_dir.display())
+            .with("PATH", path))
+    }
+
+    /// Environment of the processes running an experiment: the etna virtual environment,
+    /// the variables of the `[env]` table of `config.toml`, and the experiment id.
+    /// Values of the `[env]` table may refer to other variables as `${NAME}`, e.g.
+    /// `PATH = "/opt/coq/bin:${PATH}"` keeps the virtual environment on the path.
+    pub(crate) fn experiment(
+        etna_config: &EtnaConfig,
+        experiment_config: &ExperimentConfig,
+        experiment_id: &str,
+    ) -> anyhow::Result<Self> {
+        let mut env = Self::venv(etna_config)?;
+
+        let vars = experiment_config
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), env.expand(value)))
+            .collect::<Vec<_>>();
+        env.vars.extend(vars);
+
+        Ok(env.with("ETNA_EXPERIMENT_ID", experiment_id))
+    }
+
+    /// Replaces every `${NAME}` in `value` with the variable as it is set in this
+    /// environment, or else in the environment of etna. Unset variables are empty.
+    fn expand(&self, value: &str) -> String {
+        let mut expanded = String::new();
+        let mut rest = value;
+
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 2..start + end];
+
+            expanded.push_str(&rest[..start]);
+            match self.vars.get(name) {
+                Some(value) => expanded.push_str(value),
+                None => expanded.push_str(&std::env::var(name).unwrap_or_default()),
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        expanded.push_str(rest);
+        expanded
+    }
+
+    /// Environment of the process running a trial. The task is passed in `ETNA_TASK` as
+    /// a json object with the fields of a [`Task`](crate::run::Task), and its fields in
+    /// `ETNA_LANGUAGE`, `ETNA_WORKLOAD`, `ETNA_STRATEGY`, `ETNA_VARIANT`, `ETNA_PROPERTY`
+    /// and `ETNA_TIMEOUT`, along with the index of the trial in `ETNA_TRIAL`.
+    pub(crate) fn trial(&self, trial: &Trial) -> anyhow::Result<Self> {
+        let task = &trial.task;
+
+        Ok(self
+            .clone()
+            .with(
+                "ETNA_TASK",
+                serde_json::to_string(task).context("Failed to serialize the task")?,
+            )
+            .with("ETNA_LANGUAGE", &task.language)
+            .with("ETNA_WORKLOAD", &task.workload)
+            .with("ETNA_STRATEGY", &task.strategy)
+            .with("ETNA_VARIANT", &task.variant)
+            .with("ETNA_PROPERTY", &task.property)
+            .with("ETNA_TIMEOUT", task.timeout)
+            .with("ETNA_TRIAL", trial.trial))
+    }
+
+    pub(crate) fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.vars.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the environment on a command, on top of the environment it inherits
+    pub(crate) fn apply<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        command.envs(&self.vars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{config::GitConfig, run::Task};
+
+    fn etna_config() -> EtnaConfig {
+        EtnaConfig {
+            etna_dir: PathBuf::from("/home/user/.etna"),
+            repo_dir: PathBuf::from("/home/user/.etna/etna"),
+            venv_dir: PathBuf::from("/home/user/.etna/.venv"),
+            branch: "main".to_string(),
+            configured: true,
+            save_etna_patch: false,
+            git: GitConfig::default(),
+        }
+    }
+
+    fn task() -> Task {
+        Task {
+            language: "Coq".to_string(),
+            workload: "BST".to_string(),
+            strategy: "BespokeGenerator".to_string(),
+            variant: "insert_1".to_string(),
+            property: "prop_InsertValid".to_string(),
+            trials: 3,
+            timeout: 60.0,
+            grace: 30.0,
+            short_circuit: true,
+        }
+    }
+
+    #[test]
+    fn venv_activates_the_etna_virtual_environment() {
+        let env = ExecutionEnv::venv(&etna_config()).unwrap();
+
+        assert_eq!(env.vars["VIRTUAL_ENV"], "/home/user/.etna/.venv");
+        assert_eq!(
+            env.vars["PATH"],
+            format!(
+                "/home/user/.etna/.venv/bin:{}",
+                std::env::var("PATH").unwrap()
+            )
+        );
+        assert_eq!(env.vars.len(), 2);
+    }
+
+    #[test]
+    fn experiment_adds_the_env_table_and_the_experiment_id() {
+        let mut experiment_config =
+            ExperimentConfig::new("exp", "", PathBuf::from("/home/user/exp"));
+        experiment_config
+            .env
+            .insert("OCAMLRUNPARAM".to_string(), "b".to_string());
+
+        let env = ExecutionEnv::experiment(&etna_config(), &experiment_config, "abc123").unwrap();
+
+        assert_eq!(env.vars["OCAMLRUNPARAM"], "b");
+        assert_eq!(env.vars["ETNA_EXPERIMENT_ID"], "abc123");
+        assert_eq!(env.vars["VIRTUAL_ENV"], "/home/user/.etna/.venv");
+    }
+
+    #[test]
+    fn experiment_env_table_extends_or_overrides_the_defaults() {
+        let mut experiment_config =
+            ExperimentConfig::new("exp", "", PathBuf::from("/home/user/exp"));
+        experiment_config
+            .env
+            .insert("PATH".to_string(), "/opt/coq/bin:${PATH}".to_string());
+        experiment_config
+            .env
+            .insert("VIRTUAL_ENV".to_string(), "/opt/venv".to_string());
+
+        let env = ExecutionEnv::experiment(&etna_config(), &experiment_config, "abc123").unwrap();
+
+        // The virtual environment stays on the path, ahead of the path of etna
+        assert_eq!(
+            env.vars["PATH"],
+            format!(
+                "/opt/coq/bin:/home/user/.etna/.venv/bin:{}",
+                std::env::var("PATH").unwrap()
+            )
+        );
+        assert_eq!(env.vars["VIRTUAL_ENV"], "/opt/venv");
+    }
+
+    #[test]
+    fn expand_substitutes_variables() {
+        let env = ExecutionEnv::default().with("COQBIN", "/opt/coq/bin");
+
+        assert_eq!(env.expand("${COQBIN}/coqc"), "/opt/coq/bin/coqc");
+        assert_eq!(
+            env.expand("${COQBIN}:${ETNA_UNSET_VARIABLE}:x"),
+            "/opt/coq/bin::x"
+        );
+        assert_eq!(env.expand("$COQBIN ${COQBIN"), "$COQBIN ${COQBIN");
+    }
+
+    #[test]
+    fn experiment_id_is_not_overridden_by_the_env_table() {
+        let mut experiment_config =
+            ExperimentConfig::new("exp", "", PathBuf::from("/home/user/exp"));
+        experiment_config
+            .env
+            .insert("ETNA_EXPERIMENT_ID".to_string(), "other".to_string());
+
+        let env = ExecutionEnv::experiment(&etna_config(), &experiment_config, "abc123").unwrap();
+
+        assert_eq!(env.vars["ETNA_EXPERIMENT_ID"], "abc123");
+    }
+
+    #[test]
+    fn trial_describes_the_task_and_the_trial() {
+        let base = ExecutionEnv::default().with("ETNA_EXPERIMENT_ID", "abc123");
+        let trial = task().trials().remove(2);
+
+        let env = base.trial(&trial).unwrap();
+
+        assert_eq!(env.vars["ETNA_EXPERIMENT_ID"], "abc123");
+        assert_eq!(env.vars["ETNA_LANGUAGE"], "Coq");
+        assert_eq!(env.vars["ETNA_WORKLOAD"], "BST");
+        assert_eq!(env.vars["ETNA_STRATEGY"], "BespokeGenerator");
+        assert_eq!(env.vars["ETNA_VARIANT"], "insert_1");
+        assert_eq!(env.vars["ETNA_PROPERTY"], "prop_InsertValid");
+        assert_eq!(env.vars["ETNA_TIMEOUT"], "60");
+        assert_eq!(env.vars["ETNA_TRIAL"], "2");
+
+        let task: Task = serde_json::from_str(&env.vars["ETNA_TASK"]).unwrap();
+        assert_eq!(task, trial.task);
+        assert_eq!(task.trials, 1);
+    }
+
+    #[test]
+    fn apply_sets_the_variables_on_the_command() {
+        let env = ExecutionEnv::default().with("ETNA_TRIAL", 4);
+        let mut command = Command::new("true");
+        env.apply(&mut command);
+
+        let vars = command.get_envs().collect::<Vec<_>>();
+        assert_eq!(
+            vars,
+            vec![(
+                std::ffi::OsStr::new("ETNA_TRIAL"),
+                Some(std::ffi::OsStr::new("4"))
+            )]
+        );
+    }
+}
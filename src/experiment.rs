@@ -27,6 +27,67 @@ impl Experiment {
 pub(crate) struct ExperimentSnapshot {
     pub experiment: String,
     pub etna: String,
+    /// Hash of the etna working tree, present only when it has local changes
+    #[serde(default)]
+    pub etna_worktree: Option<String>,
     pub scripts: Vec<(String, String)>,
     pub workloads: Vec<(Workload, String)>,
 }
+
+impl ExperimentSnapshot {
+    /// Components that differ from a previous snapshot of the same experiment
+    pub(crate) fn changes(&self, previous: &ExperimentSnapshot) -> Vec<String> {
+        let mut changes = vec![];
+
+        if self.etna != previous.etna || self.etna_worktree != previous.etna_worktree {
+            changes.push("etna".to_string());
+        }
+
+        for (name, hash) in self.scripts.iter() {
+            match previous.scripts.iter().find(|(n, _)| n == name) {
+                Some((_, h)) if h == hash => {}
+                Some(_) => changes.push(name.clone()),
+                None => changes.push(format!("+{name}")),
+            }
+        }
+
+        for (name, _) in previous.scripts.iter() {
+            if !self.scripts.iter().any(|(n, _)| n == name) {
+                changes.push(format!("-{name}"));
+            }
+        }
+
+        for (workload, hash) in self.workloads.iter() {
+            let name = format!("{}/{}", workload.language, workload.name);
+            match previous.workloads.iter().find(|(w, _)| same(w, workload)) {
+                Some((_, h)) if h == hash => {}
+                // A change of source is an update from upstream rather than a local edit
+                Some((w, _)) if w.source != workload.source => match &workload.source {
+                    Some(source) => changes.push(format!(
+                        "{name} (from {})",
+                        source
+                            .commit
+                            .as_deref()
+                            .map_or(source.repo.as_str(), |c| &c[..8.min(c.len())])
+                    )),
+                    None => changes.push(name),
+                },
+                Some(_) => changes.push(name),
+                None => changes.push(format!("+{name}")),
+            }
+        }
+
+        for (workload, _) in previous.workloads.iter() {
+            if !self.workloads.iter().any(|(w, _)| same(w, workload)) {
+                changes.push(format!("-{}/{}", workload.language, workload.name));
+            }
+        }
+
+        changes
+    }
+}
+
+/// Whether two workloads are the same, regardless of their source
+fn same(w1: &Workload, w2: &Workload) -> bool {
+    w1.language == w2.language && w1.name == w2.name
+}
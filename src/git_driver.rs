@@ -4,135 +4,110 @@ use anyhow::Context;
 use log::{debug, warn};
 use tabled::settings::{Extract, Style};
 
-use crate::experiment::ExperimentSnapshot;
+use crate::{config::GitConfig, experiment::ExperimentSnapshot};
 
-pub(crate) fn initialize_git_repo(path: &PathBuf, msg: &str) -> anyhow::Result<()> {
+pub(crate) fn initialize_git_repo(
+    path: &PathBuf,
+    msg: &str,
+    git_config: &GitConfig,
+) -> anyhow::Result<()> {
     // Initialize a git repository
     let git_repo = git2::Repository::init(path).context("Failed to initialize git repository")?;
-    let mut index = git_repo.index().context("Failed to get index")?;
-    index
-        .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
-        .context("Failed to add files to index")?;
-    index.write().context("Failed to write index")?;
-    let tree_id = index.write_tree().context("Failed to write tree")?;
-    let tree = git_repo.find_tree(tree_id).context("Failed to find tree")?;
 
-    let signature = git2::Signature::now("Alperen Keles", "akeles@umd.edu")
-        .context("Failed to create signature")?;
-    git_repo
-        .commit(Some("HEAD"), &signature, &signature, msg, &tree, &[])
-        .context("Failed to commit")?;
-    Ok(())
+    commit(&git_repo, &[PathBuf::from("*")], msg, git_config)
 }
 
-pub(crate) fn commit_add_workload(
+/// Commit the given paths of an experiment, e.g. the workloads changed by a transaction
+/// and `config.toml`
+pub(crate) fn commit_paths(
     repo_path: &Path,
-    language: &str,
-    workload: &str,
+    paths: &[PathBuf],
+    msg: &str,
+    git_config: &GitConfig,
 ) -> anyhow::Result<()> {
-    // Add contents of 'workloads/<language>/<workload>' directory to the git repository
     let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
 
-    let mut index = git_repo.index().context("Failed to get index")?;
-
-    // Add the workload to the index
-    index
-        .add_all(
-            [PathBuf::from("workloads")
-                .join(language)
-                .join(workload)
-                .as_path()],
-            git2::IndexAddOption::DEFAULT,
-            None,
-        )
-        .context("Failed to add workload to index")?;
-    // Add the config file to the index
-    index
-        .add_path(PathBuf::from("config.toml").as_path())
-        .context("Failed to add workload to index")?;
-    // Commit the changes
-    let mut index = git_repo.index().context("Failed to get index")?;
-    index.write().context("Failed to write index")?;
-    let tree_id = index.write_tree().context("Failed to write tree")?;
-    let tree = git_repo.find_tree(tree_id).context("Failed to find tree")?;
-
-    let signature = git2::Signature::now("Alperen Keles", "akeles@umd.edu")
-        .context("Failed to create signature")?;
-
-    git_repo
-        .commit(
-            Some("HEAD"),
-            &signature,
-            &signature,
-            &format!("automated commit: add workload '{}/{}'", language, workload),
-            &tree,
-            &[&git_repo
-                .head()
-                .context("Failed to get head")?
-                .peel_to_commit()
-                .context("Failed to peel to commit")?],
-        )
-        .context("Failed to commit")?;
-
-    Ok(())
+    commit(&git_repo, paths, msg, git_config)
 }
 
-pub(crate) fn commit_remove_workload(
-    repo_path: &Path,
-    language: &str,
-    workload: &str,
+/// Stage the given paths, including deletions, and commit them on top of the head.
+/// The message is rendered through the configured template.
+fn commit(
+    git_repo: &git2::Repository,
+    paths: &[PathBuf],
+    msg: &str,
+    git_config: &GitConfig,
 ) -> anyhow::Result<()> {
-    // Add contents of 'workloads/<language>/<workload>' directory to the git repository
-    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
-
-    // Commit the changes
     let mut index = git_repo.index().context("Failed to get index")?;
 
-    // Add the workload to the index
+    // Drop the entries of deleted files, then add new and modified ones
     index
-        .add_all(
-            [PathBuf::from("workloads")
-                .join(language)
-                .join(workload)
-                .as_path()],
-            git2::IndexAddOption::DEFAULT,
-            None,
-        )
-        .context("Failed to add workload to index")?;
-
-    // Add the config file to the index
+        .update_all(paths, None)
+        .context("Failed to update index")?;
     index
-        .add_path(PathBuf::from("config.toml").as_path())
-        .context("Failed to add workload to index")?;
-
+        .add_all(paths, git2::IndexAddOption::DEFAULT, None)
+        .context("Failed to add files to index")?;
     index.write().context("Failed to write index")?;
+
     let tree_id = index.write_tree().context("Failed to write tree")?;
     let tree = git_repo.find_tree(tree_id).context("Failed to find tree")?;
 
-    let signature = git2::Signature::now("Alperen Keles", "akeles@umd.edu")
-        .context("Failed to create signature")?;
+    let parent = match git_repo.head() {
+        Ok(head) => Some(head.peel_to_commit().context("Failed to peel to commit")?),
+        Err(_) => None,
+    };
+
+    let signature = signature(git_repo, git_config)?;
+    let msg = match &git_config.message_template {
+        Some(template) => template.replace("{message}", msg),
+        None => msg.to_string(),
+    };
 
     git_repo
         .commit(
             Some("HEAD"),
             &signature,
             &signature,
-            &format!(
-                "automated commit: remove workload '{}/{}'",
-                language, workload
-            ),
+            &msg,
             &tree,
-            &[&git_repo
-                .head()
-                .context("Failed to get head")?
-                .peel_to_commit()
-                .context("Failed to peel to commit")?],
+            parent.iter().collect::<Vec<_>>().as_slice(),
         )
         .context("Failed to commit")?;
 
     Ok(())
 }
 
+/// The identity used for automated commits, the etna configuration takes precedence
+/// over the `user.name` and `user.email` settings of git.
+fn signature(
+    git_repo: &git2::Repository,
+    git_config: &GitConfig,
+) -> anyhow::Result<git2::Signature<'static>> {
+    let config = git_repo.config().context("Failed to get git config")?;
+
+    let name = git_config
+        .name
+        .clone()
+        .or_else(|| config.get_string("user.name").ok());
+    let email = git_config
+        .email
+        .clone()
+        .or_else(|| config.get_string("user.email").ok());
+
+    let (name, email) = match (name, email) {
+        (Some(name), Some(email)) => (name, email),
+        (name, email) => {
+            warn!("No git identity is configured, set 'user.name' and 'user.email' in git or etna configuration");
+            (
+                name.unwrap_or_else(|| "etna-cli".to_string()),
+                email.unwrap_or_else(|| "etna-cli@localhost".to_string()),
+            )
+        }
+    };
+
+    git2::Signature::now(&name, &email).context("Failed to create signature")
+}
+
 pub(crate) fn clone_etna(path: &PathBuf) -> anyhow::Result<()> {
     // Clone the etna repository
     git2::Repository::clone("https://github.com/jwshii/etna.git", path)
@@ -177,11 +152,59 @@ pub(crate) fn change_branch(repo_path: &PathBuf, branch: &str) -> anyhow::Result
     Ok(())
 }
 
+/// Clone a git repository, checking out `rev` if one is given, e.g. a branch, a tag or a
+/// commit. Returns the commit that is checked out.
+pub(crate) fn clone_at(url: &str, path: &Path, rev: Option<&str>) -> anyhow::Result<String> {
+    let git_repo =
+        git2::Repository::clone(url, path).context(format!("Failed to clone '{url}'"))?;
+
+    if let Some(rev) = rev {
+        // Branches other than the default one only exist as remote branches after cloning
+        let object = git_repo
+            .revparse_single(rev)
+            .or_else(|_| git_repo.revparse_single(&format!("origin/{rev}")))
+            .context(format!("Revision '{rev}' not found in '{url}'"))?;
+        let commit = object
+            .peel_to_commit()
+            .context(format!("Revision '{rev}' is not a commit"))?;
+
+        git_repo
+            .checkout_tree(
+                commit.as_object(),
+                Some(git2::build::CheckoutBuilder::new().force()),
+            )
+            .context(format!("Failed to check out '{rev}'"))?;
+        git_repo
+            .set_head_detached(commit.id())
+            .context("Failed to detach head")?;
+    }
+
+    head_hash(path)
+}
+
 /// Get the hash of a path in a git repository
 pub(crate) fn hash(repo_path: &Path, index_path: &Path) -> anyhow::Result<String> {
+    hash_tree(repo_path, index_path, true)
+}
+
+/// Same as [`hash`], but the blobs and trees are kept in memory instead of being written
+/// into the repository
+pub(crate) fn preview_hash(repo_path: &Path, index_path: &Path) -> anyhow::Result<String> {
+    hash_tree(repo_path, index_path, false)
+}
+
+fn hash_tree(repo_path: &Path, index_path: &Path, write: bool) -> anyhow::Result<String> {
     debug!("repo path: {}", repo_path.display());
     let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
 
+    if !write {
+        // Objects are written to the backend with the highest priority, the loose and
+        // packed backends of the repository come after it
+        let odb = git_repo.odb().context("Failed to get object database")?;
+        odb.add_new_mempack_backend(1000)
+            .context("Failed to create an in-memory object database")?;
+    }
+
     debug!("index path: {}", index_path.display());
     let mut index = git_repo.index().context("Failed to get index")?;
     index.clear().context("Failed to clear index")?;
@@ -214,6 +237,65 @@ pub(crate) fn head_hash(repo_path: &Path) -> anyhow::Result<String> {
     Ok(head.id().to_string())
 }
 
+/// Move the head of a git repository back to a commit, dropping the commits after it from
+/// the branch and the index. The working tree is left as it is.
+pub(crate) fn reset_head(repo_path: &Path, commit: &str) -> anyhow::Result<()> {
+    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
+    let oid = git2::Oid::from_str(commit).context(format!("Invalid commit '{commit}'"))?;
+    let commit = git_repo
+        .find_object(oid, Some(git2::ObjectType::Commit))
+        .context("Failed to find commit")?;
+
+    git_repo
+        .reset(&commit, git2::ResetType::Mixed, None)
+        .context("Failed to reset head")
+}
+
+/// Get the hash of the head of the git repository containing a path, if there is one
+pub(crate) fn enclosing_head_hash(path: &Path) -> Option<String> {
+    let git_repo = git2::Repository::discover(path).ok()?;
+    let head = git_repo.head().ok()?;
+    let head = head.peel_to_commit().ok()?;
+    Some(head.id().to_string())
+}
+
+/// Get the hash of the tree pointed by the head of a git repository
+pub(crate) fn head_tree_hash(repo_path: &Path) -> anyhow::Result<String> {
+    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
+    let head = git_repo.head().context("Failed to get head")?;
+    let tree = head.peel_to_tree().context("Failed to peel to tree")?;
+    Ok(tree.id().to_string())
+}
+
+/// Get the local changes of a git repository, relative to its head, as a patch
+pub(crate) fn worktree_patch(repo_path: &Path) -> anyhow::Result<String> {
+    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
+    let head = git_repo.head().context("Failed to get head")?;
+    let tree = head.peel_to_tree().context("Failed to peel to tree")?;
+
+    let mut options = git2::DiffOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+
+    let diff = git_repo
+        .diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))
+        .context("Failed to diff the working tree")?;
+
+    let mut patch = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin() as u8);
+        }
+        patch.extend_from_slice(line.content());
+        true
+    })
+    .context("Failed to format the patch")?;
+
+    Ok(String::from_utf8_lossy(&patch).to_string())
+}
+
 /// Writes out the diff of two git hashes
 pub(crate) fn print_diff(s1: &ExperimentSnapshot, s2: &ExperimentSnapshot) -> anyhow::Result<()> {
     let mut table = vec![
@@ -226,6 +308,15 @@ pub(crate) fn print_diff(s1: &ExperimentSnapshot, s2: &ExperimentSnapshot) -> an
         ("etna".to_string(), s1.etna.clone(), s2.etna.clone()),
     ];
 
+    if s1.etna_worktree.is_some() || s2.etna_worktree.is_some() {
+        let worktree = |s: &ExperimentSnapshot| {
+            s.etna_worktree
+                .clone()
+                .unwrap_or_else(|| "clean".to_string())
+        };
+        table.push(("etna (worktree)".to_string(), worktree(s1), worktree(s2)));
+    }
+
     table.extend(
         s1.scripts
             .iter()
@@ -238,11 +329,20 @@ pub(crate) fn print_diff(s1: &ExperimentSnapshot, s2: &ExperimentSnapshot) -> an
             .iter()
             .zip(s2.workloads.iter())
             .map(|(w1, w2)| {
-                (
-                    w1.0.name.clone() + "-" + &w1.0.language,
-                    w1.1.clone(),
-                    w2.1.clone(),
-                )
+                // Workloads updated from their source are labeled with the source commit
+                let label = match (&w1.0.source, &w2.0.source) {
+                    (Some(s1), s2) if Some(s1) != s2.as_ref() => format!(
+                        "{}-{} ({})",
+                        w1.0.name,
+                        w1.0.language,
+                        s1.commit
+                            .as_deref()
+                            .map_or("updated", |c| &c[..8.min(c.len())])
+                    ),
+                    _ => w1.0.name.clone() + "-" + &w1.0.language,
+                };
+
+                (label, w1.1.clone(), w2.1.clone())
             }),
     );
 
@@ -256,3 +356,204 @@ pub(crate) fn print_diff(s1: &ExperimentSnapshot, s2: &ExperimentSnapshot) -> an
 
     Ok(())
 }
+
+/// Copy a tree, along with every object it references, from a git repository into the
+/// bare archive at `archive_path`. The archive is created if it does not exist.
+pub(crate) fn archive_tree(
+    repo_path: &Path,
+    archive_path: &Path,
+    tree_id: &str,
+) -> anyhow::Result<()> {
+    if !archive_path.exists() {
+        git2::Repository::init_bare(archive_path).context("Failed to initialize the archive")?;
+    }
+
+    copy_tree(repo_path, archive_path, tree_id)
+        .context(format!("Failed to archive tree '{tree_id}'"))
+}
+
+/// Copy a tree, along with every object it references, between two git repositories
+pub(crate) fn copy_tree(from_path: &Path, to_path: &Path, tree_id: &str) -> anyhow::Result<()> {
+    let from = git2::Repository::open(from_path).context(format!(
+        "Failed to open git repository '{}'",
+        from_path.display()
+    ))?;
+    let to = git2::Repository::open(to_path).context(format!(
+        "Failed to open git repository '{}'",
+        to_path.display()
+    ))?;
+
+    let oid = git2::Oid::from_str(tree_id).context(format!("Invalid tree id '{tree_id}'"))?;
+    let to_odb = to.odb().context("Failed to get object database")?;
+
+    copy_object(&from, &to_odb, oid)
+}
+
+/// Copies the children of an object before the object itself, so an object present
+/// in the destination is always complete.
+fn copy_object(from: &git2::Repository, to: &git2::Odb, oid: git2::Oid) -> anyhow::Result<()> {
+    if to.exists(oid) {
+        return Ok(());
+    }
+
+    let odb = from.odb().context("Failed to get object database")?;
+    let object = odb.read(oid).context(format!(
+        "Object '{oid}' not found in '{}'",
+        from.path().display()
+    ))?;
+
+    if object.kind() == git2::ObjectType::Tree {
+        let tree = from.find_tree(oid).context("Failed to find tree")?;
+        for entry in tree.iter() {
+            // Submodules are stored as commits of another repository, they cannot be copied
+            if matches!(
+                entry.kind(),
+                Some(git2::ObjectType::Tree) | Some(git2::ObjectType::Blob)
+            ) {
+                copy_object(from, to, entry.id())?;
+            }
+        }
+    }
+
+    to.write(object.kind(), object.data())
+        .context(format!("Failed to write object '{oid}'"))?;
+
+    Ok(())
+}
+
+/// Check that a tree, and every object it references, exists in a git repository
+pub(crate) fn contains_tree(repo_path: &Path, tree_id: &str) -> anyhow::Result<bool> {
+    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
+    let oid = git2::Oid::from_str(tree_id).context(format!("Invalid tree id '{tree_id}'"))?;
+
+    is_complete(&git_repo, oid)
+}
+
+fn is_complete(git_repo: &git2::Repository, oid: git2::Oid) -> anyhow::Result<bool> {
+    let Ok(tree) = git_repo.find_tree(oid) else {
+        return Ok(false);
+    };
+
+    let odb = git_repo.odb().context("Failed to get object database")?;
+    for entry in tree.iter() {
+        let complete = match entry.kind() {
+            Some(git2::ObjectType::Tree) => is_complete(git_repo, entry.id())?,
+            Some(git2::ObjectType::Blob) => odb.exists(entry.id()),
+            _ => true,
+        };
+
+        if !complete {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+pub(crate) enum Merge {
+    Merged(String),
+    Conflicts(Vec<String>),
+}
+
+/// Three-way merge of trees of a git repository, returning the merged tree or the
+/// paths that conflict
+pub(crate) fn merge_trees(
+    repo_path: &Path,
+    base: &str,
+    ours: &str,
+    theirs: &str,
+) -> anyhow::Result<Merge> {
+    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
+    let find_tree = |tree_id: &str| -> anyhow::Result<git2::Tree> {
+        let oid = git2::Oid::from_str(tree_id).context(format!("Invalid tree id '{tree_id}'"))?;
+        git_repo
+            .find_tree(oid)
+            .context(format!("Failed to find tree '{tree_id}'"))
+    };
+
+    let mut index = git_repo
+        .merge_trees(
+            &find_tree(base)?,
+            &find_tree(ours)?,
+            &find_tree(theirs)?,
+            None,
+        )
+        .context("Failed to merge trees")?;
+
+    if index.has_conflicts() {
+        let mut paths = vec![];
+        for conflict in index.conflicts().context("Failed to get conflicts")? {
+            let conflict = conflict.context("Failed to read conflict")?;
+            let entry = conflict
+                .our
+                .or(conflict.their)
+                .or(conflict.ancestor)
+                .context("Conflict without entries")?;
+            paths.push(String::from_utf8_lossy(&entry.path).to_string());
+        }
+        paths.sort();
+        paths.dedup();
+
+        return Ok(Merge::Conflicts(paths));
+    }
+
+    let tree = index
+        .write_tree_to(&git_repo)
+        .context("Failed to write merged tree")?;
+
+    Ok(Merge::Merged(tree.to_string()))
+}
+
+/// Brings the working directory from one tree to another, only touching the files that
+/// differ between them, so untracked files such as build artifacts are kept.
+pub(crate) fn apply_tree_diff(repo_path: &Path, from: &str, to: &str) -> anyhow::Result<()> {
+    let git_repo = git2::Repository::open(repo_path).context("Failed to open git repository")?;
+    let from = git_repo
+        .find_tree(git2::Oid::from_str(from).context(format!("Invalid tree id '{from}'"))?)
+        .context("Failed to find tree")?;
+    let to = git_repo
+        .find_tree(git2::Oid::from_str(to).context(format!("Invalid tree id '{to}'"))?)
+        .context("Failed to find tree")?;
+
+    let diff = git_repo
+        .diff_tree_to_tree(Some(&from), Some(&to), None)
+        .context("Failed to diff trees")?;
+
+    for delta in diff.deltas() {
+        if let Some(path) = delta.old_file().path() {
+            if delta.status() == git2::Delta::Deleted || delta.status() == git2::Delta::Renamed {
+                let path = repo_path.join(path);
+                if path.exists() {
+                    std::fs::remove_file(&path)
+                        .context(format!("Failed to remove '{}'", path.display()))?;
+                }
+            }
+        }
+
+        if delta.status() == git2::Delta::Deleted {
+            continue;
+        }
+
+        let file = delta.new_file();
+        let path = repo_path.join(file.path().context("Diff entry without a path")?);
+        let blob = git_repo
+            .find_blob(file.id())
+            .context(format!("Failed to find blob of '{}'", path.display()))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create '{}'", parent.display()))?;
+        }
+        std::fs::write(&path, blob.content())
+            .context(format!("Failed to write '{}'", path.display()))?;
+
+        #[cfg(unix)]
+        if file.mode() == git2::FileMode::BlobExecutable {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                .context(format!("Failed to make '{}' executable", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
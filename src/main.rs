@@ -1,11 +1,19 @@
 
+mod catalog;
+mod checkpoint;
 mod commands;
 mod config;
+mod execution;
 mod experiment;
 mod git_driver;
+mod marker;
 mod python_driver;
+mod run;
+mod runner;
+mod scheduler;
 mod snapshot;
 mod store;
+mod transaction;
 mod workload;
 mod cli;
 
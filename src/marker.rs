@@ -0,0 +1,436 @@
+/// Comment delimiters of the languages whose sources can hold mutant markers
+const COMMENTS: [(&str, &str); 3] = [("(*", "*)"), ("{-", "-}"), ("#|", "|#")];
+
+/// A marked region of a source file, holding the original code and its mutant variants
+///
+/// ```coq
+/// (*! *)
+/// T E k E
+/// (*!! insert_1 *)
+/// (*!
+/// E
+/// *)
+/// (* !*)
+/// ```
+#[derive(Debug)]
+pub(crate) struct Mutation {
+    /// Line of the opening marker, starting from 1
+    pub line: usize,
+    /// Line of the closing marker
+    pub end: usize,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Variant {
+    pub name: String,
+    /// Line of the variant marker, starting from 1
+    pub line: usize,
+    /// Code replacing the original code of the mutation when the variant is active
+    pub code: Vec<String>,
+}
+
+/// A marker that does not fit in a mutation
+#[derive(Debug)]
+pub(crate) struct MarkerError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug)]
+enum Marker {
+    /// `(*! *)`, starts a mutation
+    Begin,
+    /// `(*!! name *)`, starts a variant
+    Variant(String),
+    /// `(*! ...`, starts the code of a variant, possibly ending on the same line
+    Body { code: String, closed: bool },
+    /// `(* !*)`, ends a mutation
+    End,
+}
+
+/// Where the parser is within a mutation
+enum State {
+    Outside,
+    Original,
+    Variant,
+    /// Inside the code of a variant, until the comment closes
+    Body(&'static str),
+    AfterBody,
+}
+
+/// Parses the mutations of a source file, along with the markers that are out of place
+pub(crate) fn parse(source: &str) -> (Vec<Mutation>, Vec<MarkerError>) {
+    let mut mutations: Vec<Mutation> = vec![];
+    let mut errors = vec![];
+    let mut state = State::Outside;
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let mut error = |message: &str| {
+            errors.push(MarkerError {
+                line: number,
+                message: message.to_string(),
+            })
+        };
+
+        // Inside the code of a variant, only the end of the comment matters
+        if let State::Body(close) = state {
+            let (code, closed) = match line.trim_end().strip_suffix(close) {
+                Some(code) => (code, true),
+                None => (line, false),
+            };
+
+            if !closed || !code.trim().is_empty() {
+                push_code(&mut mutations, code);
+            }
+            if closed {
+                state = State::AfterBody;
+            }
+            continue;
+        }
+
+        let Some((marker, (open, close))) = marker(line) else {
+            continue;
+        };
+
+        state = match (state, marker) {
+            (State::Outside, Marker::Begin) => {
+                mutations.push(Mutation {
+                    line: number,
+                    end: number,
+                    variants: vec![],
+                });
+                State::Original
+            }
+            (State::Original | State::AfterBody, Marker::Variant(name)) => {
+                let mutation = mutations.last_mut().expect("a mutation is open");
+
+                if name.is_empty() {
+                    error("Variant marker without a name");
+                } else if mutation.variants.iter().any(|v| v.name == name) {
+                    error(&format!("Variant '{name}' is declared twice in a mutation"));
+                }
+
+                mutation.variants.push(Variant {
+                    name,
+                    line: number,
+                    code: vec![],
+                });
+                State::Variant
+            }
+            (State::Variant, Marker::Body { code, closed }) => {
+                if !code.trim().is_empty() {
+                    push_code(&mut mutations, &code);
+                }
+
+                if closed {
+                    State::AfterBody
+                } else {
+                    State::Body(close)
+                }
+            }
+            // A variant removing the original code, e.g. `(*! *)`
+            (State::Variant, Marker::Begin) => State::AfterBody,
+            (State::AfterBody, Marker::End) => {
+                if let Some(mutation) = mutations.last_mut() {
+                    mutation.end = number;
+                }
+                State::Outside
+            }
+            (State::Original, Marker::End) => {
+                error("Mutation has no variants");
+                State::Outside
+            }
+            (State::Variant, _) => {
+                error(&format!(
+                    "Variant has no code, expected a comment opening with '{open}!'"
+                ));
+                State::Outside
+            }
+            (State::Outside, _) => {
+                error("Marker outside of a mutation, expected a mutation to start here");
+                State::Outside
+            }
+            (_, Marker::Begin) => {
+                error("Mutation starts before the previous one ends");
+                State::Original
+            }
+            (state, _) => {
+                error("Unexpected marker");
+                state
+            }
+        };
+    }
+
+    match state {
+        State::Outside => {}
+        State::Body(_) => errors.push(MarkerError {
+            line: source.lines().count(),
+            message: "The code of a variant is never closed".to_string(),
+        }),
+        _ => errors.push(MarkerError {
+            line: mutations.last().map(|m| m.line).unwrap_or_default(),
+            message: "Mutation is never closed".to_string(),
+        }),
+    }
+
+    (mutations, errors)
+}
+
+/// The marker on a line, if any, along with the comment delimiters it uses
+fn marker(line: &str) -> Option<(Marker, (&'static str, &'static str))> {
+    let line = line.trim();
+
+    COMMENTS.iter().find_map(|(open, close)| {
+        let rest = line.strip_prefix(open)?;
+
+        let marker = if let Some(name) = rest.strip_prefix("!!") {
+            let name = name.trim().trim_end_matches(close).trim();
+            Marker::Variant(name.to_string())
+        } else if let Some(code) = rest.strip_prefix('!') {
+            if code.trim() == *close {
+                Marker::Begin
+            } else {
+                let code = code.trim_end();
+                match code.strip_suffix(close) {
+                    Some(code) => Marker::Body {
+                        code: code.trim().to_string(),
+                        closed: true,
+                    },
+                    None => Marker::Body {
+                        code: code.trim().to_string(),
+                        closed: false,
+                    },
+                }
+            }
+        } else if rest.trim_start().strip_prefix('!')? == *close {
+            Marker::End
+        } else {
+            return None;
+        };
+
+        Some((marker, (*open, *close)))
+    })
+}
+
+/// Adds a line to the code of the last variant
+fn push_code(mutations: &mut [Mutation], code: &str) {
+    if let Some(variant) = mutations.last_mut().and_then(|m| m.variants.last_mut()) {
+        variant.code.push(code.to_string());
+    }
+}
+
+/// The source with a variant active, replacing the original code of the mutations that
+/// hold it, and keeping the other mutations as they are. `None` if the source has no
+/// such variant.
+pub(crate) fn apply(source: &str, mutations: &[Mutation], variant: &str) -> Option<String> {
+    let active = mutations
+        .iter()
+        .filter_map(|m| {
+            let variant = m.variants.iter().find(|v| v.name == variant)?;
+            Some((m, variant))
+        })
+        .collect::<Vec<_>>();
+
+    if active.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec![];
+    let mut skip_until = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        if number <= skip_until {
+            continue;
+        }
+
+        match active.iter().find(|(m, _)| m.line == number) {
+            Some((mutation, variant)) => {
+                lines.extend(variant.code.iter().map(String::as_str));
+                skip_until = mutation.end;
+            }
+            None => lines.push(line),
+        }
+    }
+
+    let mut applied = lines.join("\n");
+    if source.ends_with('\n') {
+        applied.push('\n');
+    }
+
+    Some(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(errors: &[MarkerError]) -> Vec<(usize, &str)> {
+        errors
+            .iter()
+            .map(|e| (e.line, e.message.as_str()))
+            .collect()
+    }
+
+    const COQ: &str = "\
+match t with
+(*! *)
+| E => T E k E
+(*!! insert_1 *)
+(*!
+| E => E
+*)
+(*!! insert_2 *)
+(*! | E => T E k v *)
+(* !*)
+end
+";
+
+    #[test]
+    fn parses_coq_markers() {
+        let (mutations, errors) = parse(COQ);
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(mutations.len(), 1);
+        assert_eq!((mutations[0].line, mutations[0].end), (2, 10));
+
+        let variants = &mutations[0].variants;
+        assert_eq!(variants[0].name, "insert_1");
+        assert_eq!(variants[0].line, 4);
+        assert_eq!(variants[0].code, ["| E => E"]);
+        assert_eq!(variants[1].name, "insert_2");
+        assert_eq!(variants[1].code, ["| E => T E k v"]);
+    }
+
+    #[test]
+    fn parses_haskell_markers() {
+        let source = "\
+insert k t = case t of
+{-! -}
+  E -> T E k E
+{-!! insert_1 -}
+{-!
+  E -> E
+  _ -> t
+-}
+{- !-}
+";
+        let (mutations, errors) = parse(source);
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!((mutations[0].line, mutations[0].end), (2, 9));
+        assert_eq!(mutations[0].variants[0].name, "insert_1");
+        assert_eq!(mutations[0].variants[0].code, ["  E -> E", "  _ -> t"]);
+    }
+
+    #[test]
+    fn parses_racket_markers() {
+        let source = "\
+#|! |#
+[(E) (T E k E)]
+#|!! insert_1 |#
+#|! [(E) E] |#
+#| !|#
+";
+        let (mutations, errors) = parse(source);
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!((mutations[0].line, mutations[0].end), (1, 5));
+        assert_eq!(mutations[0].variants[0].code, ["[(E) E]"]);
+    }
+
+    #[test]
+    fn variant_may_remove_the_original_code() {
+        let source = "\
+a
+(*! *)
+b
+(*!! delete *)
+(*! *)
+(* !*)
+c
+";
+        let (mutations, errors) = parse(source);
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert!(mutations[0].variants[0].code.is_empty());
+        assert_eq!(
+            apply(source, &mutations, "delete").as_deref(),
+            Some("a\nc\n")
+        );
+    }
+
+    #[test]
+    fn unclosed_markers_are_errors() {
+        let (_, errors) = parse("(*! *)\na\n(*!! v *)\n(*! b *)\n");
+        assert_eq!(messages(&errors), [(1, "Mutation is never closed")]);
+
+        let (_, errors) = parse("(*! *)\na\n(*!! v *)\n(*!\nb\n");
+        assert_eq!(
+            messages(&errors),
+            [(5, "The code of a variant is never closed")]
+        );
+    }
+
+    #[test]
+    fn misplaced_markers_are_errors() {
+        let (_, errors) = parse("(*!! v *)\n(* !*)\n");
+        assert_eq!(
+            messages(&errors),
+            [
+                (
+                    1,
+                    "Marker outside of a mutation, expected a mutation to start here"
+                ),
+                (
+                    2,
+                    "Marker outside of a mutation, expected a mutation to start here"
+                ),
+            ]
+        );
+
+        let (_, errors) = parse("(*! *)\na\n(* !*)\n");
+        assert_eq!(messages(&errors), [(3, "Mutation has no variants")]);
+    }
+
+    #[test]
+    fn duplicate_variants_are_errors() {
+        let source = "\
+(*! *)
+a
+(*!! v *)
+(*! b *)
+(*!! v *)
+(*! c *)
+(* !*)
+";
+        let (mutations, errors) = parse(source);
+
+        assert_eq!(
+            messages(&errors),
+            [(5, "Variant 'v' is declared twice in a mutation")]
+        );
+        assert_eq!(mutations[0].variants.len(), 2);
+    }
+
+    #[test]
+    fn apply_replaces_the_mutations_holding_the_variant() {
+        let source = format!("{COQ}(*! *)\nx\n(*!! other *)\n(*! y *)\n(* !*)");
+        let (mutations, errors) = parse(&source);
+        assert!(errors.is_empty(), "{errors:?}");
+
+        let applied = apply(&source, &mutations, "insert_1").unwrap();
+        // Only the mutation holding the variant is replaced, along with its markers
+        assert_eq!(
+            applied,
+            "match t with\n| E => E\nend\n(*! *)\nx\n(*!! other *)\n(*! y *)\n(* !*)"
+        );
+
+        let applied = apply(&source, &mutations, "insert_2").unwrap();
+        assert!(applied.starts_with("match t with\n| E => T E k v\nend\n"));
+
+        assert!(apply(&source, &mutations, "missing").is_none());
+    }
+}
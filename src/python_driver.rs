@@ -1,42 +1,26 @@
 use anyhow::{Context, Ok};
 use log::{debug, info};
 
-use crate::{
-    config::{EtnaConfig, ExperimentConfig},
-    experiment::ExperimentSnapshot,
-};
-
-pub(crate) fn run_experiment(
-    etna_config: &EtnaConfig,
-    _experiment_config: &ExperimentConfig,
-    snapshot: ExperimentSnapshot,
-) -> anyhow::Result<()> {
-    std::env::set_var("ETNA_EXPERIMENT_ID", snapshot.experiment);
-    debug!(
-        "ETNA_EXPERIMENT_ID={:?}",
-        std::env::var("ETNA_EXPERIMENT_ID")
-    );
-
-    std::env::set_var("VIRTUAL_ENV", etna_config.venv_dir.display().to_string());
-    debug!("VIRTUAL_ENV={:?}", std::env::var("VIRTUAL_ENV"));
-
-    std::env::set_var(
-        "PATH",
-        format!(
-            "{}/bin:{}",
-            std::env::var("VIRTUAL_ENV")
-                .context("VIRTUAL_ENV is not present in the environment")?,
-            std::env::var("PATH").context("PATH is not present in the environment")?
-        ),
-    );
-    debug!("PATH={:?}", std::env::var("PATH"));
+use crate::{config::EtnaConfig, execution::ExecutionEnv, run::ExpectedBreaks};
+
+/// Gets the properties each mutant variant is expected to break from benchtool
+pub(crate) fn expected_breaks(etna_config: &EtnaConfig) -> anyhow::Result<ExpectedBreaks> {
+    let output = std::process::Command::new(etna_config.venv_dir.join("bin").join("python3"))
+        .args([
+            "-c",
+            "import json; from benchtool.Tasks import tasks; print(json.dumps(tasks))",
+        ])
+        .output()
+        .context("Failed to run python in the etna virtual environment")?;
 
-    std::process::Command::new("python3")
-        .args(["Collect.py"])
-        .status()
-        .context("Failed to run the experiment")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to import benchtool: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
 
-    Ok(())
+    serde_json::from_slice(&output.stdout).context("Failed to parse the benchtool tasks")
 }
 
 pub(crate) fn make(etna_config: &EtnaConfig) -> anyhow::Result<()> {
@@ -53,24 +37,13 @@ pub(crate) fn make(etna_config: &EtnaConfig) -> anyhow::Result<()> {
             .context("Failed to create virtual environment")?;
     }
 
-    std::env::set_var("VIRTUAL_ENV", &etna_venv_dir);
-    debug!("VIRTUAL_ENV={:?}", std::env::var("VIRTUAL_ENV"));
-
-    std::env::set_var(
-        "PATH",
-        format!(
-            "{}/bin:{}",
-            std::env::var("VIRTUAL_ENV")
-                .context("VIRTUAL_ENV is not present in the environment")?,
-            std::env::var("PATH").context("PATH is not present in the environment")?
-        ),
-    );
-    debug!("PATH={:?}", std::env::var("PATH"));
-
     debug!("make -C {} install", etna_config.repo_dir.display());
 
-    let output = std::process::Command::new("make")
-        .args(["-C", &etna_config.repo_dir.display().to_string(), "install"])
+    let mut command = std::process::Command::new("make");
+    command.args(["-C", &etna_config.repo_dir.display().to_string(), "install"]);
+
+    let output = ExecutionEnv::venv(etna_config)?
+        .apply(&mut command)
         .output()
         .context(format!(
             "Failed to run ETNA setup script at {}",
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    python_driver,
+    scheduler::Trial,
+    workload,
+};
+
+/// Selection of the tasks to run given on the command line, on top of the run matrix.
+/// An empty selection keeps every task of the matrix, and a missing `trials` or `timeout`
+/// keeps the one of the matrix. Names are matched case-insensitively, properties with or
+/// without the `prop_` prefix.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RunFilter {
+    pub workloads: Vec<String>,
+    pub strategies: Vec<String>,
+    pub variants: Vec<String>,
+    pub properties: Vec<String>,
+    pub trials: Option<usize>,
+    pub timeout: Option<f64>,
+}
+
+/// Run Matrix
+/// The `[matrix]` table of `config.toml`, declaring which tasks an experiment runs.
+/// Every workload runs all of its variants, strategies and properties unless narrowed
+/// down in `[matrix.workloads."<language>/<name>"]`.
+///
+/// ```toml
+/// [matrix]
+/// trials = 10
+/// timeout = 60.0
+/// grace = 60.0
+/// short_circuit = true
+///
+/// [matrix.workloads."Coq/BST".strategies]
+/// include = ["BespokeGenerator", "TypeBasedGenerator"]
+///
+/// [matrix.workloads."Coq/BST".variants]
+/// exclude = ["insert_3"]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct RunMatrix {
+    #[serde(default = "RunMatrix::default_trials")]
+    pub trials: usize,
+    #[serde(default = "RunMatrix::default_timeout")]
+    pub timeout: f64,
+    /// Seconds a trial is given on top of its timeout, e.g. to build the workload,
+    /// before it is killed
+    #[serde(default = "RunMatrix::default_grace")]
+    pub grace: f64,
+    /// Skip the remaining trials of a task once one of them is killed
+    #[serde(default = "RunMatrix::default_short_circuit")]
+    pub short_circuit: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub workloads: BTreeMap<String, WorkloadMatrix>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct WorkloadMatrix {
+    #[serde(default)]
+    pub strategies: Selection,
+    #[serde(default)]
+    pub variants: Selection,
+    #[serde(default)]
+    pub properties: Selection,
+}
+
+/// An empty `include` list selects everything, `exclude` is applied afterwards
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Selection {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+/// A single (workload, strategy, variant, property) combination, along with its settings.
+/// Every trial of a task runs the collection script once, with the task in `ETNA_TASK`
+/// and its fields in the other `ETNA_*` variables, see [`ExecutionEnv::trial`].
+///
+/// [`ExecutionEnv::trial`]: crate::execution::ExecutionEnv::trial
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Task {
+    pub language: String,
+    pub workload: String,
+    pub strategy: String,
+    pub variant: String,
+    pub property: String,
+    pub trials: usize,
+    pub timeout: f64,
+    pub grace: f64,
+    pub short_circuit: bool,
+}
+
+impl Task {
+    /// Identifies the task among all the tasks of an experiment
+    pub(crate) fn id(&self) -> String {
+        format!(
+            "{}/{}/{}/{}/{}",
+            self.language, self.workload, self.variant, self.strategy, self.property
+        )
+    }
+
+    /// Seconds after which a trial of the task is killed
+    pub(crate) fn deadline(&self) -> f64 {
+        self.timeout + self.grace
+    }
+
+    /// Splits the task into its trials, each running a single trial of the task
+    pub(crate) fn trials(&self) -> Vec<Trial> {
+        (0..self.trials)
+            .map(|trial| Trial {
+                task: Task {
+                    trials: 1,
+                    ..self.clone()
+                },
+                trial,
+            })
+            .collect()
+    }
+}
+
+/// The properties each mutant variant is expected to break, by workload and variant name
+pub(crate) type ExpectedBreaks = BTreeMap<String, BTreeMap<String, Vec<String>>>;
+
+impl Default for RunMatrix {
+    fn default() -> Self {
+        Self {
+            trials: Self::default_trials(),
+            timeout: Self::default_timeout(),
+            grace: Self::default_grace(),
+            short_circuit: Self::default_short_circuit(),
+            workloads: BTreeMap::new(),
+        }
+    }
+}
+
+impl RunMatrix {
+    fn default_trials() -> usize {
+        10
+    }
+
+    fn default_timeout() -> f64 {
+        60.0
+    }
+
+    fn default_grace() -> f64 {
+        60.0
+    }
+
+    fn default_short_circuit() -> bool {
+        true
+    }
+
+    /// Expands the matrix into the concrete list of tasks for the workloads of an experiment.
+    /// Variant and property pairs missing from `expected_breaks` are skipped, for the
+    /// workloads it knows about.
+    pub(crate) fn expand(
+        &self,
+        experiment_config: &ExperimentConfig,
+        expected_breaks: &ExpectedBreaks,
+        filter: &RunFilter,
+    ) -> anyhow::Result<Vec<Task>> {
+        let mut tasks = vec![];
+
+        for workload in experiment_config.workloads.iter() {
+            let key = format!("{}/{}", workload.language, workload.name);
+            if !selected(&filter.workloads, &[&workload.name, &key]) {
+                continue;
+            }
+
+            let contents = workload::inspect(&workload.path(&experiment_config.path))
+                .context(format!("Failed to inspect workload '{key}'"))?;
+
+            let matrix = self
+                .workloads
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(&key))
+                .map(|(_, matrix)| matrix.clone())
+                .unwrap_or_default();
+
+            let breaks = expected_breaks
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(&workload.name))
+                .map(|(_, breaks)| breaks);
+
+            if breaks.is_none() && !expected_breaks.is_empty() {
+                warn!("No expected breaks are known for '{key}', running every property against every variant");
+            }
+
+            for variant in contents.variants.iter() {
+                if !matrix.variants.selects(&[variant]) || !selected(&filter.variants, &[variant]) {
+                    continue;
+                }
+
+                for strategy in contents.strategies.iter() {
+                    if !matrix.strategies.selects(&[strategy])
+                        || !selected(&filter.strategies, &[strategy])
+                    {
+                        continue;
+                    }
+
+                    for property in contents.properties.iter() {
+                        let short = property.trim_start_matches("prop_");
+                        if !matrix.properties.selects(&[property, short])
+                            || !selected(&filter.properties, &[property, short])
+                        {
+                            continue;
+                        }
+
+                        if let Some(breaks) = breaks {
+                            let expected = breaks
+                                .get(variant)
+                                .is_some_and(|properties| properties.iter().any(|p| p == short));
+                            if !expected {
+                                continue;
+                            }
+                        }
+
+                        tasks.push(Task {
+                            language: workload.language.clone(),
+                            workload: workload.name.clone(),
+                            strategy: strategy.clone(),
+                            variant: variant.clone(),
+                            property: property.clone(),
+                            trials: filter.trials.unwrap_or(self.trials),
+                            timeout: filter.timeout.unwrap_or(self.timeout),
+                            grace: self.grace,
+                            short_circuit: self.short_circuit,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(tasks)
+    }
+}
+
+impl Selection {
+    fn selects(&self, names: &[&str]) -> bool {
+        selected(&self.include, names) && !matches(&self.exclude, names)
+    }
+}
+
+/// An empty selection selects everything
+fn selected(selection: &[String], names: &[&str]) -> bool {
+    selection.is_empty() || matches(selection, names)
+}
+
+fn matches(selection: &[String], names: &[&str]) -> bool {
+    selection
+        .iter()
+        .any(|s| names.iter().any(|name| s.eq_ignore_ascii_case(name)))
+}
+
+/// Expands the run matrix of an experiment, using the expected breaks known to benchtool
+pub(crate) fn plan(
+    etna_config: &EtnaConfig,
+    experiment_config: &ExperimentConfig,
+    filter: &RunFilter,
+) -> anyhow::Result<Vec<Task>> {
+    let expected_breaks = python_driver::expected_breaks(etna_config).unwrap_or_else(|e| {
+        warn!("Failed to get the expected breaks from benchtool: {e:#}");
+        ExpectedBreaks::new()
+    });
+
+    experiment_config
+        .matrix
+        .expand(experiment_config, &expected_breaks, filter)
+}
+
+/// Worst-case duration of a list of tasks in seconds, every trial running until it is killed
+pub(crate) fn worst_case_duration(tasks: &[Task]) -> f64 {
+    tasks
+        .iter()
+        .map(|task| task.trials as f64 * task.deadline())
+        .sum()
+}
@@ -0,0 +1,214 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use log::debug;
+use serde_derive::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    execution::ExecutionEnv,
+    scheduler::{self, Trial},
+    workload::{self, Workload},
+};
+
+/// Runner Configuration
+/// The `[runner]` table of `config.toml`, choosing how the trials of an experiment are run.
+/// Every runner receives the trial in the environment, see [`ExecutionEnv::trial`].
+///
+/// ```toml
+/// # Run the collection script in the etna virtual environment, the default
+/// [runner]
+/// type = "python"
+///
+/// # Run a shell command from the experiment directory
+/// [runner]
+/// type = "shell"
+/// command = "./collect.sh"
+///
+/// # Build and test a copy of the workload with the variant of the trial applied
+/// [runner]
+/// type = "native"
+/// build = "make"
+/// test = "make test STRATEGY=$ETNA_STRATEGY PROPERTY=$ETNA_PROPERTY"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum RunnerConfig {
+    #[default]
+    Python,
+    Shell {
+        command: String,
+    },
+    Native {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        build: Option<String>,
+        test: String,
+    },
+}
+
+/// Builds the process running a single trial of an experiment
+pub(crate) trait Runner: Sync {
+    /// The process running the trial, as shown by a dry run
+    fn command(&self, trial: &Trial) -> anyhow::Result<Command>;
+
+    /// Sets up the trial right before it runs, returning its process along with the
+    /// scratch directory it runs in, if any, which is removed once dropped
+    fn prepare(&self, trial: &Trial) -> anyhow::Result<(Command, Option<TempDir>)> {
+        Ok((self.command(trial)?, None))
+    }
+
+    /// Whether the trials of a workload must run one at a time, as the runner applies their
+    /// variants to the sources of the workload in place
+    fn exclusive(&self) -> bool {
+        true
+    }
+}
+
+/// Runs the collection script of the experiment with benchtool, in the etna virtual environment
+struct PythonRunner {
+    env: ExecutionEnv,
+    script: PathBuf,
+    dir: PathBuf,
+}
+
+/// Runs a shell command from the experiment directory
+struct ShellRunner {
+    env: ExecutionEnv,
+    command: String,
+    dir: PathBuf,
+}
+
+/// Builds and tests a workload without going through benchtool. Each trial runs in a
+/// scratch copy of the workload with its variant applied, see [`workload::materialize`].
+struct NativeRunner {
+    env: ExecutionEnv,
+    build: Option<String>,
+    test: String,
+    root: PathBuf,
+}
+
+/// The runner configured for an experiment
+pub(crate) fn runner(
+    etna_config: &EtnaConfig,
+    experiment_config: &ExperimentConfig,
+    experiment_id: &str,
+) -> anyhow::Result<Box<dyn Runner>> {
+    let env = ExecutionEnv::experiment(etna_config, experiment_config, experiment_id)?;
+    let dir = experiment_config.path.clone();
+
+    let runner: Box<dyn Runner> = match &experiment_config.runner {
+        RunnerConfig::Python => {
+            let script = experiment_config.path.join(&experiment_config.script);
+            if !script.exists() {
+                anyhow::bail!(
+                    "The collection script '{}' of the experiment {} does not exist",
+                    script.display(),
+                    experiment_config.name
+                );
+            }
+
+            Box::new(PythonRunner {
+                env,
+                script: experiment_config.script.clone(),
+                dir,
+            })
+        }
+        RunnerConfig::Shell { command } => Box::new(ShellRunner {
+            env,
+            command: command.clone(),
+            dir,
+        }),
+        RunnerConfig::Native { build, test } => Box::new(NativeRunner {
+            env,
+            build: build.clone(),
+            test: test.clone(),
+            root: dir,
+        }),
+    };
+
+    Ok(runner)
+}
+
+impl Runner for PythonRunner {
+    fn command(&self, trial: &Trial) -> anyhow::Result<Command> {
+        let mut command = Command::new("python3");
+        command.arg(&self.script).current_dir(&self.dir);
+        self.env.trial(trial)?.apply(&mut command);
+
+        debug!("{:?}", command);
+
+        Ok(command)
+    }
+}
+
+impl Runner for ShellRunner {
+    fn command(&self, trial: &Trial) -> anyhow::Result<Command> {
+        let mut command = Command::new("sh");
+        command.args(["-c", &self.command]).current_dir(&self.dir);
+        self.env.trial(trial)?.apply(&mut command);
+
+        debug!("{:?}", command);
+
+        Ok(command)
+    }
+}
+
+impl Runner for NativeRunner {
+    fn command(&self, trial: &Trial) -> anyhow::Result<Command> {
+        self.command_in(trial, &workload_of(trial).path(&self.root))
+    }
+
+    fn prepare(&self, trial: &Trial) -> anyhow::Result<(Command, Option<TempDir>)> {
+        let workload = workload_of(trial);
+        let variant = &trial.task.variant;
+
+        let scratch = scheduler::private_dir(&format!(
+            "etna-{}-{}-{variant}-",
+            workload.language, workload.name
+        ))
+        .context("Failed to create the scratch directory of the trial")?;
+
+        let dir = scratch.path().join(&workload.name);
+        workload::materialize(&workload.path(&self.root), &dir, variant).context(format!(
+            "Failed to apply '{variant}' to '{}/{}'",
+            workload.language, workload.name
+        ))?;
+
+        Ok((self.command_in(trial, &dir)?, Some(scratch)))
+    }
+
+    /// Every trial runs in its own copy of the workload
+    fn exclusive(&self) -> bool {
+        false
+    }
+}
+
+impl NativeRunner {
+    fn command_in(&self, trial: &Trial, dir: &Path) -> anyhow::Result<Command> {
+        // The test only runs once the build succeeds
+        let script = match &self.build {
+            Some(build) => format!("({build}) && ({})", self.test),
+            None => self.test.clone(),
+        };
+
+        let mut command = Command::new("sh");
+        command.args(["-c", &script]).current_dir(dir);
+        self.env.trial(trial)?.apply(&mut command);
+
+        debug!("{:?}", command);
+
+        Ok(command)
+    }
+}
+
+fn workload_of(trial: &Trial) -> Workload {
+    Workload {
+        language: trial.task.language.clone(),
+        name: trial.task.workload.clone(),
+        source: None,
+    }
+}
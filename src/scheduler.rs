@@ -0,0 +1,691 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    process::{Command, ExitStatus, Stdio},
+    sync::{mpsc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, warn};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::run::Task;
+
+/// How often a running trial is polled for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Seconds a trial may run past its CPU limit, after being sent SIGXCPU, before the
+/// kernel kills it
+const CPU_LIMIT_SLACK: u64 = 5;
+
+/// CPU time may be accounted slightly short of the limit when a process is stopped by it
+const CPU_ACCOUNTING_SLACK: Duration = Duration::from_millis(100);
+
+/// Limits
+/// The `[limits]` table of `config.toml`, restricting the resources of every process
+/// of a trial. Limits are applied with setrlimit, on Linux only. Trials always run in
+/// their own process group, so they can be killed along with their children.
+///
+/// A trial is classified as exceeding its CPU limit when it is terminated by the signal of
+/// the limit and its CPU time shows that the limit was reached, see [`outcome`]. The process
+/// reaching the limit is often a descendant of the trial, its signal is only seen when every
+/// process in between passes it up the way shells do, exiting with 128 + the signal. The
+/// shell and native runners do, through `sh`. The python runner does only if the collection
+/// script does, otherwise its trials stopped by a limit are recorded as failed or completed.
+/// A process running out of memory fails to allocate, and crashes like any other failing
+/// process, so these trials are recorded as failed.
+///
+/// ```toml
+/// [limits]
+/// cpu = 600          # seconds of CPU time
+/// memory = 8192      # megabytes of address space
+/// files = 1024       # open files
+/// private_tmp = true # a fresh TMPDIR for each trial, only accessible to the user and
+///                    # removed afterwards
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Limits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
+    #[serde(default)]
+    pub private_tmp: bool,
+}
+
+/// A single trial of a task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Trial {
+    #[serde(flatten)]
+    pub task: Task,
+    pub trial: usize,
+}
+
+impl Trial {
+    /// The workload whose sources the trial modifies when its runner applies the variant
+    /// in place, see [`Runner::exclusive`](crate::runner::Runner::exclusive)
+    fn resource(&self) -> String {
+        format!("{}/{}", self.task.language, self.task.workload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Outcome {
+    Completed,
+    Failed,
+    TimedOut,
+    /// Terminated on reaching one of its [`Limits`]
+    LimitExceeded,
+}
+
+/// The result of a trial, recorded as a metric in the store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct TrialResult {
+    #[serde(flatten)]
+    pub trial: Trial,
+    pub outcome: Outcome,
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the trial
+    pub signal: Option<i32>,
+    /// Wall-clock duration of the trial in seconds
+    pub duration: f64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Summary {
+    pub completed: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    pub limit_exceeded: usize,
+    pub skipped: usize,
+}
+
+struct Queue {
+    pending: VecDeque<Trial>,
+    busy: HashSet<String>,
+    /// Whether trials of the same workload wait for each other
+    exclusive: bool,
+    /// Tasks whose remaining trials are skipped after a timeout
+    short_circuited: HashSet<String>,
+    skipped: usize,
+}
+
+/// Runs the trials in a pool of `jobs` workers.
+/// `command` builds the process running a trial, along with a guard kept until the trial
+/// finishes, e.g. its scratch directory. `on_result` is called on the calling thread as soon
+/// as a trial finishes. A trial running past its deadline is killed along with every
+/// process it spawned. When `exclusive` is set, trials of the same workload never run
+/// concurrently.
+pub(crate) fn run<G>(
+    trials: Vec<Trial>,
+    jobs: usize,
+    limits: &Limits,
+    exclusive: bool,
+    command: impl Fn(&Trial) -> anyhow::Result<(Command, G)> + Sync,
+    mut on_result: impl FnMut(&TrialResult) -> anyhow::Result<()>,
+) -> anyhow::Result<Summary> {
+    let queue = Mutex::new(Queue {
+        pending: trials.into(),
+        busy: HashSet::new(),
+        exclusive,
+        short_circuited: HashSet::new(),
+        skipped: 0,
+    });
+    let available = Condvar::new();
+    let (sender, receiver) = mpsc::channel::<TrialResult>();
+
+    let mut summary = Summary::default();
+
+    std::thread::scope(|scope| -> anyhow::Result<()> {
+        for worker in 0..jobs.max(1) {
+            let sender = sender.clone();
+            let (queue, available, command) = (&queue, &available, &command);
+
+            scope.spawn(move || {
+                while let Some(trial) = next_trial(queue, available) {
+                    debug!("worker {worker} running {:?}", trial);
+
+                    let result = match command(&trial) {
+                        Ok((command, _guard)) => execute(trial, command, limits),
+                        Err(e) => {
+                            error!("Failed to prepare the trial: {e:#}");
+                            TrialResult {
+                                trial,
+                                outcome: Outcome::Failed,
+                                exit_code: None,
+                                signal: None,
+                                duration: 0.0,
+                            }
+                        }
+                    };
+
+                    let mut queue = queue.lock().unwrap();
+                    queue.busy.remove(&result.trial.resource());
+                    if result.outcome == Outcome::TimedOut && result.trial.task.short_circuit {
+                        queue.short_circuited.insert(result.trial.task.id());
+                    }
+                    drop(queue);
+                    available.notify_all();
+
+                    if sender.send(result).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Only the workers hold senders now, the loop ends when they are all done
+        drop(sender);
+
+        for result in receiver.iter() {
+            match result.outcome {
+                Outcome::Completed => summary.completed += 1,
+                Outcome::Failed => summary.failed += 1,
+                Outcome::TimedOut => summary.timed_out += 1,
+                Outcome::LimitExceeded => summary.limit_exceeded += 1,
+            }
+
+            if let Err(e) = on_result(&result) {
+                // Stop handing out trials, the running ones are left to finish
+                queue.lock().unwrap().pending.clear();
+                available.notify_all();
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    })?;
+
+    summary.skipped = queue.into_inner().unwrap().skipped;
+
+    Ok(summary)
+}
+
+/// Takes the first pending trial whose workload is not in use by an exclusive trial,
+/// waiting if there is none.
+/// Returns `None` once there are no pending trials left.
+fn next_trial(queue: &Mutex<Queue>, available: &Condvar) -> Option<Trial> {
+    let mut queue = queue.lock().unwrap();
+
+    loop {
+        let Queue {
+            pending,
+            short_circuited,
+            ..
+        } = &mut *queue;
+        let before = pending.len();
+        pending.retain(|trial| !short_circuited.contains(&trial.task.id()));
+        queue.skipped += before - queue.pending.len();
+
+        if queue.pending.is_empty() {
+            return None;
+        }
+
+        let position = queue
+            .pending
+            .iter()
+            .position(|trial| !queue.exclusive || !queue.busy.contains(&trial.resource()));
+
+        if let Some(position) = position {
+            let trial = queue.pending.remove(position)?;
+            if queue.exclusive {
+                queue.busy.insert(trial.resource());
+            }
+            return Some(trial);
+        }
+
+        queue = available.wait(queue).unwrap();
+    }
+}
+
+fn execute(trial: Trial, mut command: Command, limits: &Limits) -> TrialResult {
+    command.stdin(Stdio::null());
+
+    // Run the trial in its own process group, so it can be killed with all its children
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    apply_limits(&mut command, limits);
+
+    let tmp = if limits.private_tmp {
+        match private_dir("etna-trial-") {
+            Ok(tmp) => Some(tmp),
+            Err(e) => {
+                error!("Failed to create the temporary directory of the trial: {e}");
+                return TrialResult {
+                    trial,
+                    outcome: Outcome::Failed,
+                    exit_code: None,
+                    signal: None,
+                    duration: 0.0,
+                };
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Some(tmp) = &tmp {
+        let path = tmp.path();
+        command
+            .env("TMPDIR", path)
+            .env("TMP", path)
+            .env("TEMP", path);
+    }
+
+    let deadline = Duration::from_secs_f64(trial.task.deadline());
+    let start = Instant::now();
+
+    let (outcome, status) = match command.spawn() {
+        Ok(mut child) => loop {
+            match try_wait(&mut child) {
+                Ok(Some((status, cpu))) => break (outcome(status, cpu, limits), Some(status)),
+                Ok(None) if start.elapsed() > deadline => {
+                    warn!(
+                        "Killing trial {} of {} after {:.0}s",
+                        trial.trial,
+                        trial.task.id(),
+                        deadline.as_secs_f64()
+                    );
+                    kill_tree(&mut child);
+                    break (Outcome::TimedOut, None);
+                }
+                Ok(None) => std::thread::sleep(POLL_INTERVAL),
+                Err(e) => {
+                    error!("Failed to wait for the trial: {e}");
+                    kill_tree(&mut child);
+                    break (Outcome::Failed, None);
+                }
+            }
+        },
+        Err(e) => {
+            error!("Failed to start the trial: {e}");
+            (Outcome::Failed, None)
+        }
+    };
+
+    if let Some(tmp) = tmp {
+        let path = tmp.path().to_path_buf();
+        if let Err(e) = tmp.close() {
+            warn!(
+                "Failed to remove the temporary directory '{}': {e}",
+                path.display()
+            );
+        }
+    }
+
+    if outcome == Outcome::LimitExceeded {
+        warn!(
+            "Trial {} of {} was terminated by a resource limit",
+            trial.trial,
+            trial.task.id()
+        );
+    }
+
+    TrialResult {
+        trial,
+        outcome,
+        exit_code: status.and_then(|status| status.code()),
+        signal: status.and_then(signal),
+        duration: start.elapsed().as_secs_f64(),
+    }
+}
+
+/// Sets the resource limits in the child, right before it starts the trial
+#[cfg(target_os = "linux")]
+fn apply_limits(command: &mut Command, limits: &Limits) {
+    let mut rlimits = vec![];
+    if let Some(cpu) = limits.cpu {
+        rlimits.push((libc::RLIMIT_CPU, cpu, cpu + CPU_LIMIT_SLACK));
+    }
+    if let Some(memory) = limits.memory {
+        let bytes = memory.saturating_mul(1024 * 1024);
+        rlimits.push((libc::RLIMIT_AS, bytes, bytes));
+    }
+    if let Some(files) = limits.files {
+        rlimits.push((libc::RLIMIT_NOFILE, files, files));
+    }
+
+    if rlimits.is_empty() {
+        return;
+    }
+
+    // setrlimit is async-signal-safe, so it can be called between fork and exec
+    unsafe {
+        std::os::unix::process::CommandExt::pre_exec(command, move || {
+            for (resource, soft, hard) in rlimits.iter() {
+                let limit = libc::rlimit {
+                    rlim_cur: *soft as libc::rlim_t,
+                    rlim_max: *hard as libc::rlim_t,
+                };
+                if libc::setrlimit(*resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_limits(_command: &mut Command, limits: &Limits) {
+    if limits.cpu.is_some() || limits.memory.is_some() || limits.files.is_some() {
+        warn!("Resource limits are only supported on Linux, running the trial without them");
+    }
+}
+
+/// The exit status of the trial if it has finished, along with the CPU time used by the
+/// trial and the descendants it waited for
+#[cfg(target_os = "linux")]
+fn try_wait(child: &mut std::process::Child) -> std::io::Result<Option<(ExitStatus, Duration)>> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // Safety: rusage is plain data, wait4 fills it in
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    let pid = unsafe {
+        libc::wait4(
+            child.id() as libc::pid_t,
+            &mut status,
+            libc::WNOHANG,
+            &mut usage,
+        )
+    };
+
+    match pid {
+        0 => Ok(None),
+        pid if pid < 0 => Err(std::io::Error::last_os_error()),
+        _ => {
+            let time = |t: libc::timeval| {
+                Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+            };
+            let cpu = time(usage.ru_utime) + time(usage.ru_stime);
+
+            Ok(Some((ExitStatus::from_raw(status), cpu)))
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn try_wait(child: &mut std::process::Child) -> std::io::Result<Option<(ExitStatus, Duration)>> {
+    Ok(child.try_wait()?.map(|status| (status, Duration::ZERO)))
+}
+
+/// A trial is considered terminated by its CPU limit when it is killed by SIGXCPU, sent
+/// by the kernel on reaching the limit, or by SIGKILL, sent on reaching the hard limit,
+/// and its `cpu` time reached the limit. The signal is either the one that killed the
+/// trial, or the one passed up by a shell as an exit code of 128 + the signal, when the
+/// process reaching the limit is a descendant of the trial.
+fn outcome(status: ExitStatus, cpu: Duration, limits: &Limits) -> Outcome {
+    if status.success() {
+        return Outcome::Completed;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let signal = signal(status).or_else(|| {
+            status
+                .code()
+                .filter(|code| (129..=128 + 64).contains(code))
+                .map(|code| code - 128)
+        });
+
+        // The time of the trial includes the time of the process that reached the limit
+        let reached = limits
+            .cpu
+            .is_some_and(|limit| cpu + CPU_ACCOUNTING_SLACK >= Duration::from_secs(limit));
+
+        if reached && matches!(signal, Some(libc::SIGXCPU | libc::SIGKILL)) {
+            return Outcome::LimitExceeded;
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = (cpu, limits);
+
+    Outcome::Failed
+}
+
+/// Creates a fresh directory with a random name in the temporary directory, accessible to
+/// the user only, so that it is never shared with another trial or user
+pub(crate) fn private_dir(prefix: &str) -> std::io::Result<tempfile::TempDir> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(prefix);
+
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+
+    builder.tempdir()
+}
+
+fn signal(status: ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    return std::os::unix::process::ExitStatusExt::signal(&status);
+
+    #[cfg(not(unix))]
+    None
+}
+
+fn kill_tree(child: &mut std::process::Child) {
+    // The trial is the leader of its process group, killing the group reaches every
+    // process it spawned
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    fn trial() -> Trial {
+        Trial {
+            task: Task {
+                language: "Coq".to_string(),
+                workload: "BST".to_string(),
+                strategy: "BespokeGenerator".to_string(),
+                variant: "insert_1".to_string(),
+                property: "prop_InsertValid".to_string(),
+                trials: 1,
+                timeout: 10.0,
+                grace: 0.0,
+                short_circuit: true,
+            },
+            trial: 0,
+        }
+    }
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    fn cpu_limited() -> Limits {
+        Limits {
+            cpu: Some(600),
+            ..Default::default()
+        }
+    }
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn outcome_of_a_signal_killing_the_trial() {
+        let xcpu = ExitStatus::from_raw(libc::SIGXCPU);
+        let kill = ExitStatus::from_raw(libc::SIGKILL);
+
+        assert_eq!(
+            outcome(xcpu, seconds(600), &cpu_limited()),
+            Outcome::LimitExceeded
+        );
+        assert_eq!(
+            outcome(kill, seconds(605), &cpu_limited()),
+            Outcome::LimitExceeded
+        );
+        // Signals sent before the limit is reached, or without a limit, are failures
+        assert_eq!(outcome(xcpu, seconds(1), &cpu_limited()), Outcome::Failed);
+        assert_eq!(outcome(kill, seconds(1), &cpu_limited()), Outcome::Failed);
+        assert_eq!(
+            outcome(xcpu, seconds(600), &Limits::default()),
+            Outcome::Failed
+        );
+    }
+
+    #[test]
+    fn outcome_of_a_signal_passed_up_by_a_shell() {
+        let xcpu = ExitStatus::from_raw((128 + libc::SIGXCPU) << 8);
+        let kill = ExitStatus::from_raw((128 + libc::SIGKILL) << 8);
+
+        assert_eq!(
+            outcome(xcpu, seconds(600), &cpu_limited()),
+            Outcome::LimitExceeded
+        );
+        assert_eq!(
+            outcome(kill, seconds(605), &cpu_limited()),
+            Outcome::LimitExceeded
+        );
+        // A plain exit code is a failure unless the CPU time shows the limit was reached
+        assert_eq!(outcome(kill, seconds(1), &cpu_limited()), Outcome::Failed);
+        assert_eq!(
+            outcome(kill, seconds(600), &Limits::default()),
+            Outcome::Failed
+        );
+    }
+
+    #[test]
+    fn crashes_are_failures_under_a_memory_limit() {
+        let limits = Limits {
+            memory: Some(1024),
+            ..Default::default()
+        };
+
+        for signal in [libc::SIGSEGV, libc::SIGABRT, libc::SIGBUS] {
+            assert_eq!(
+                outcome(ExitStatus::from_raw(signal), seconds(1), &limits),
+                Outcome::Failed
+            );
+            assert_eq!(
+                outcome(
+                    ExitStatus::from_raw((128 + signal) << 8),
+                    seconds(1),
+                    &limits
+                ),
+                Outcome::Failed
+            );
+        }
+    }
+
+    #[test]
+    fn outcome_of_an_ordinary_exit() {
+        assert_eq!(
+            outcome(ExitStatus::from_raw(0), seconds(600), &cpu_limited()),
+            Outcome::Completed
+        );
+        assert_eq!(
+            outcome(ExitStatus::from_raw(1 << 8), seconds(600), &cpu_limited()),
+            Outcome::Failed
+        );
+    }
+
+    #[test]
+    fn limit_reached_by_a_grandchild_is_classified() {
+        let limits = Limits {
+            cpu: Some(1),
+            ..Default::default()
+        };
+
+        // The inner shell stands for the build or test process of a trial, spinning until
+        // the kernel stops it
+        let mut command = sh("sh -c 'while :; do :; done'");
+        command.stderr(Stdio::null());
+        let result = execute(trial(), command, &limits);
+
+        assert_eq!(result.outcome, Outcome::LimitExceeded);
+        assert_eq!(result.exit_code, Some(128 + libc::SIGXCPU));
+    }
+
+    #[test]
+    fn signal_sent_by_a_process_is_a_failure() {
+        let mut command = sh("sh -c 'kill -XCPU $$'");
+        command.stderr(Stdio::null());
+        let result = execute(trial(), command, &cpu_limited());
+
+        assert_eq!(result.outcome, Outcome::Failed);
+    }
+
+    /// Runs two trials of the same workload with two jobs, each waiting up to `wait` seconds
+    /// for the other to start. Returns whether they both saw each other running.
+    fn overlap(exclusive: bool, wait: u32) -> bool {
+        let dir = tempfile::tempdir().unwrap();
+        let trials = vec![
+            trial(),
+            Trial {
+                trial: 1,
+                ..trial()
+            },
+        ];
+
+        let mut outcomes = vec![];
+        run(
+            trials,
+            2,
+            &Limits::default(),
+            exclusive,
+            |trial| {
+                let mut command = sh(&format!(
+                    "touch {n}; i=0; while [ ! -f {other} ] && [ $i -lt {ticks} ]; \
+                     do sleep 0.1; i=$((i+1)); done; test -f {other}",
+                    n = trial.trial,
+                    other = 1 - trial.trial,
+                    ticks = wait * 10,
+                ));
+                command.current_dir(dir.path());
+                Ok((command, ()))
+            },
+            |result| {
+                outcomes.push(result.outcome);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        outcomes == [Outcome::Completed, Outcome::Completed]
+    }
+
+    #[test]
+    fn trials_of_a_workload_overlap_unless_exclusive() {
+        assert!(overlap(false, 10));
+        assert!(!overlap(true, 1));
+    }
+
+    #[test]
+    fn private_tmp_is_fresh_private_and_removed() {
+        let limits = Limits {
+            private_tmp: true,
+            ..Default::default()
+        };
+        let script = r#"test "$(stat -c %a "$TMPDIR")" = 700 && test -z "$(ls -A "$TMPDIR")" && echo "$TMPDIR" > "$0""#;
+
+        let record = tempfile::NamedTempFile::new().unwrap();
+        let mut command = sh(script);
+        command.arg(record.path());
+
+        let result = execute(trial(), command, &limits);
+        assert_eq!(result.outcome, Outcome::Completed);
+
+        let tmp = std::fs::read_to_string(record.path()).unwrap();
+        assert!(tmp.trim().contains("etna-trial-"));
+        assert!(!std::path::Path::new(tmp.trim()).exists());
+    }
+}
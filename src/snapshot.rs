@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 
+use anyhow::Context;
 use log::debug;
 use serde_derive::{Deserialize, Serialize};
 
@@ -15,7 +16,18 @@ pub(crate) struct Snapshot {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum SnapshotType {
     #[serde(rename = "etna")]
-    Etna { branch: String },
+    Etna {
+        branch: String,
+        /// Hash of the working tree, computed the same way as the experiment snapshots
+        #[serde(default)]
+        tree: Option<String>,
+        /// Whether the working tree differs from the head commit
+        #[serde(default)]
+        dirty: bool,
+        /// Path to the patch holding the local changes, if it was saved
+        #[serde(default)]
+        patch: Option<PathBuf>,
+    },
     #[serde(rename = "script")]
     Script { name: String },
     #[serde(rename = "workload")]
@@ -47,22 +59,75 @@ impl SnapshotType {
     }
 }
 
+impl std::fmt::Display for SnapshotType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Etna { branch, .. } => write!(f, "etna ({branch})"),
+            Self::Script { name } => write!(f, "script ({name})"),
+            Self::Workload { name, language } => write!(f, "workload ({language}/{name})"),
+            Self::Experiment { time } => write!(f, "experiment ({time})"),
+        }
+    }
+}
+
 impl Snapshot {
-    pub(crate) fn head(repo_path: &Path, typ: SnapshotType) -> anyhow::Result<Self> {
+    /// The git tree the snapshot refers to, if it is known.
+    /// Etna snapshots are identified by a commit, so their tree is recorded separately.
+    pub(crate) fn tree(&self) -> Option<&str> {
+        match &self.typ {
+            SnapshotType::Etna { tree, .. } => tree.as_deref(),
+            _ => Some(&self.hash),
+        }
+    }
+
+    /// Snapshot of the etna repository, recording both the head commit and the working tree.
+    /// If the working tree has local changes, they are written as a patch into `patch_dir`.
+    /// Without `write`, the working tree is hashed without writing objects into the repository.
+    pub(crate) fn etna(
+        repo_path: &Path,
+        branch: &str,
+        patch_dir: Option<&Path>,
+        write: bool,
+    ) -> anyhow::Result<Self> {
         let hash = git_driver::head_hash(repo_path)?;
+        let head_tree = git_driver::head_tree_hash(repo_path)?;
+        let tree = hash_tree(repo_path, Path::new("*"), write)?;
+        let dirty = tree != head_tree;
+
+        debug!("etna head tree = {}, working tree = {}", head_tree, tree);
+
+        let patch = match patch_dir {
+            Some(patch_dir) if dirty => {
+                std::fs::create_dir_all(patch_dir).context("Failed to create patch directory")?;
+                let patch_path = patch_dir.join(format!("{tree}.patch"));
+                std::fs::write(&patch_path, git_driver::worktree_patch(repo_path)?)
+                    .context(format!("Failed to write patch '{}'", patch_path.display()))?;
+                Some(patch_path)
+            }
+            _ => None,
+        };
+
         Ok(Self {
             path: repo_path.to_path_buf(),
-            typ,
+            typ: SnapshotType::Etna {
+                branch: branch.to_string(),
+                tree: Some(tree),
+                dirty,
+                patch,
+            },
             hash,
         })
     }
 
+    /// Hashes `index_path` in the repository at `repo_path`, without `write` no objects are
+    /// written into the repository
     pub(crate) fn take(
         repo_path: &Path,
         index_path: &Path,
         typ: SnapshotType,
+        write: bool,
     ) -> anyhow::Result<Self> {
-        let hash = git_driver::hash(repo_path, index_path)?;
+        let hash = hash_tree(repo_path, index_path, write)?;
         debug!(
             "hash of {} = {}",
             repo_path.join(index_path).display(),
@@ -76,3 +141,11 @@ impl Snapshot {
         })
     }
 }
+
+fn hash_tree(repo_path: &Path, index_path: &Path, write: bool) -> anyhow::Result<String> {
+    if write {
+        git_driver::hash(repo_path, index_path)
+    } else {
+        git_driver::preview_hash(repo_path, index_path)
+    }
+}
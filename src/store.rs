@@ -1,11 +1,17 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Ok};
+use log::warn;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{
     config::{EtnaConfig, ExperimentConfig},
     experiment::{Experiment, ExperimentSnapshot},
+    git_driver,
+    scheduler::Trial,
     snapshot::{self, Snapshot, SnapshotType},
     workload::Workload,
 };
@@ -15,6 +21,8 @@ pub(crate) struct Store {
     pub metrics: Vec<Metric>,
     pub snapshots: HashSet<Snapshot>,
     pub experiments: HashSet<Experiment>,
+    #[serde(default)]
+    pub runs: Vec<Run>,
 }
 
 impl Store {
@@ -23,6 +31,7 @@ impl Store {
             metrics: Vec::new(),
             snapshots: HashSet::new(),
             experiments: HashSet::new(),
+            runs: Vec::new(),
         }
     }
 
@@ -37,10 +46,72 @@ impl Store {
         Ok(store)
     }
 
+    /// Writes the store to a temporary file next to `path` and renames it into place,
+    /// so that readers never see a partially written store
     pub(crate) fn save(&self, path: &PathBuf) -> anyhow::Result<()> {
         let content = serde_json::to_string_pretty(self)?;
 
-        std::fs::write(path, content).context("Failed to write store file")
+        let file_name = path
+            .file_name()
+            .context("The store path has no file name")?
+            .to_string_lossy();
+        let temp_path = path.with_file_name(format!(".{file_name}.tmp-{}", std::process::id()));
+
+        std::fs::write(&temp_path, content).context("Failed to write store file")?;
+        std::fs::rename(&temp_path, path).map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            anyhow::anyhow!(e).context("Failed to replace store file")
+        })
+    }
+
+    /// Takes the exclusive lock of the store at `path`, held until the returned file is
+    /// dropped. Every read-modify-write of the store happens under this lock, trials running
+    /// in parallel and `etna store write` update the store concurrently.
+    pub(crate) fn lock(path: &Path) -> anyhow::Result<std::fs::File> {
+        let lock_path = path.with_extension("json.lock");
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .context(format!("Failed to open '{}'", lock_path.display()))?;
+
+        file.lock()
+            .context(format!("Failed to lock '{}'", lock_path.display()))?;
+
+        Ok(file)
+    }
+
+    /// Loads the store at `path`, applies `update` to it and saves it, holding the lock
+    /// of the store throughout
+    pub(crate) fn update<T>(
+        path: &PathBuf,
+        update: impl FnOnce(&mut Store) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let _lock = Self::lock(path)?;
+
+        let mut store = Self::load(path).context("Failed to load the store")?;
+        let result = update(&mut store)?;
+        store.save(path).context("Failed to save the store")?;
+
+        Ok(result)
+    }
+
+    /// Adds a metric to the store at `path`
+    pub(crate) fn append_metric(path: &PathBuf, metric: Metric) -> anyhow::Result<()> {
+        Self::update(path, |store| {
+            store.metrics.push(metric);
+            Ok(())
+        })
+    }
+
+    /// Adds or replaces a run in the store at `path`
+    pub(crate) fn save_run(path: &PathBuf, run: &Run) -> anyhow::Result<()> {
+        Self::update(path, |store| {
+            store.runs.retain(|r| r.id != run.id);
+            store.runs.push(run.clone());
+            Ok(())
+        })
     }
 
     pub(crate) fn take_snapshot(
@@ -48,15 +119,56 @@ impl Store {
         etna_config: &EtnaConfig,
         experiment_config: &ExperimentConfig,
     ) -> anyhow::Result<ExperimentSnapshot> {
-        let etna_snapshot = snapshot::Snapshot::head(
-            &etna_config.repo_dir,
-            SnapshotType::Etna {
-                branch: etna_config.branch.clone(),
-            },
-        )
-        .context("Failed to take etna snapshot")?;
+        let patch_dir = etna_config.patches_path();
+        let (experiment_snapshot, snapshots) = Self::collect_snapshots(
+            etna_config,
+            experiment_config,
+            etna_config.save_etna_patch.then_some(patch_dir.as_path()),
+            true,
+        )?;
+
+        for (repo_path, snapshot) in snapshots {
+            self.archive(etna_config, repo_path, &snapshot)?;
+            self.snapshots.insert(snapshot);
+        }
+
+        Ok(experiment_snapshot)
+    }
 
-        self.snapshots.insert(etna_snapshot.clone());
+    /// Computes the snapshot of an experiment without recording anything, neither in
+    /// the store, the object archive nor the object databases of the repositories.
+    pub(crate) fn preview_snapshot(
+        etna_config: &EtnaConfig,
+        experiment_config: &ExperimentConfig,
+    ) -> anyhow::Result<ExperimentSnapshot> {
+        Self::collect_snapshots(etna_config, experiment_config, None, false)
+            .map(|(experiment_snapshot, _)| experiment_snapshot)
+    }
+
+    /// Takes the snapshots making up the snapshot of an experiment, along with the
+    /// repository each of them was taken from. Without `write`, their trees are only hashed.
+    fn collect_snapshots<'a>(
+        etna_config: &'a EtnaConfig,
+        experiment_config: &'a ExperimentConfig,
+        patch_dir: Option<&Path>,
+        write: bool,
+    ) -> anyhow::Result<(ExperimentSnapshot, Vec<(&'a Path, Snapshot)>)> {
+        let etna_snapshot =
+            snapshot::Snapshot::etna(&etna_config.repo_dir, &etna_config.branch, patch_dir, write)
+                .context("Failed to take etna snapshot")?;
+
+        let etna_worktree = match &etna_snapshot.typ {
+            SnapshotType::Etna {
+                tree, dirty: true, ..
+            } => {
+                warn!(
+                    "The etna repository at '{}' has uncommitted changes",
+                    etna_config.repo_dir.display()
+                );
+                tree.clone()
+            }
+            _ => None,
+        };
 
         let experiment_snapshot = snapshot::Snapshot::take(
             &experiment_config.path,
@@ -64,23 +176,24 @@ impl Store {
             snapshot::SnapshotType::Experiment {
                 time: chrono::Utc::now().to_rfc3339(),
             },
+            write,
         )
         .context("Failed to take experiment snapshot")?;
 
-        self.snapshots.insert(experiment_snapshot.clone());
-
         let collection_script_snapshot = snapshot::Snapshot::take(
             &experiment_config.path,
-            &PathBuf::from("Collect.py"),
+            &experiment_config.script,
             snapshot::SnapshotType::Script {
-                name: "Collect.py".to_string(),
+                name: experiment_config.script_name(),
             },
+            write,
         )
-        .context("Failed to take Collect.py snapshot")?;
-
-        self.snapshots.insert(collection_script_snapshot.clone());
+        .context(format!(
+            "Failed to take {} snapshot",
+            experiment_config.script_name()
+        ))?;
 
-        let workload_snapshots: Vec<(Workload, String)> = experiment_config
+        let workload_snapshots: Vec<(Workload, Snapshot)> = experiment_config
             .workloads
             .iter()
             .map(|workload| {
@@ -94,21 +207,62 @@ impl Store {
                         name: workload.name.clone(),
                         language: workload.language.clone(),
                     },
+                    write,
                 )
                 .context("Failed to take workloads snapshot")?;
-                self.snapshots.insert(workload_snapshot.clone());
 
-                Ok((workload.clone(), workload_snapshot.hash))
+                Ok((workload.clone(), workload_snapshot))
             })
             .filter_map(Result::ok)
             .collect();
 
-        Ok(ExperimentSnapshot {
-            experiment: experiment_snapshot.hash,
-            etna: etna_snapshot.hash,
-            scripts: vec![("Collect.py".to_string(), collection_script_snapshot.hash)],
-            workloads: workload_snapshots,
-        })
+        let snapshot = ExperimentSnapshot {
+            experiment: experiment_snapshot.hash.clone(),
+            etna: etna_snapshot.hash.clone(),
+            etna_worktree,
+            scripts: vec![(
+                experiment_config.script_name(),
+                collection_script_snapshot.hash.clone(),
+            )],
+            workloads: workload_snapshots
+                .iter()
+                .map(|(workload, snapshot)| (workload.clone(), snapshot.hash.clone()))
+                .collect(),
+        };
+
+        let experiment_path = experiment_config.path.as_path();
+        let mut snapshots = vec![
+            (etna_config.repo_dir.as_path(), etna_snapshot),
+            (experiment_path, experiment_snapshot),
+            (experiment_path, collection_script_snapshot),
+        ];
+        snapshots.extend(
+            workload_snapshots
+                .into_iter()
+                .map(|(_, snapshot)| (experiment_path, snapshot)),
+        );
+
+        Ok((snapshot, snapshots))
+    }
+
+    /// Copy the tree of a snapshot into the store's object archive, so it stays
+    /// recoverable even if the repository it was taken from is removed.
+    fn archive(
+        &self,
+        etna_config: &EtnaConfig,
+        repo_path: &Path,
+        snapshot: &Snapshot,
+    ) -> anyhow::Result<()> {
+        if let Some(tree) = snapshot.tree() {
+            git_driver::archive_tree(repo_path, &etna_config.objects_path(), tree).context(
+                format!(
+                    "Failed to archive the snapshot of '{}'",
+                    snapshot.path.display()
+                ),
+            )?;
+        }
+
+        Ok(())
     }
 }
 
@@ -154,6 +308,35 @@ impl Store {
             .collect::<Vec<&Experiment>>()
     }
 
+    /// All the experiments with the given name along with their snapshot times, oldest first
+    pub(crate) fn get_experiment_history(&self, name: &str) -> Vec<(&Experiment, Option<&str>)> {
+        let mut history = self
+            .get_all_experiments_by_name(name)
+            .into_iter()
+            .map(|experiment| {
+                let time = self
+                    .snapshots
+                    .iter()
+                    .find_map(|snapshot| match &snapshot.typ {
+                        SnapshotType::Experiment { time } if snapshot.hash == experiment.id => {
+                            Some(time.as_str())
+                        }
+                        _ => None,
+                    });
+                (experiment, time)
+            })
+            .collect::<Vec<_>>();
+
+        history.sort_by_key(|(experiment, time)| {
+            (
+                time.and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok()),
+                experiment.id.clone(),
+            )
+        });
+
+        history
+    }
+
     pub(crate) fn get_experiment_by_id(&self, hash: &str) -> anyhow::Result<&Experiment> {
         self.experiments
             .iter()
@@ -168,6 +351,64 @@ pub(crate) struct Metric {
     pub experiment_id: String,
 }
 
+/// A single invocation of `etna experiment run`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Run {
+    pub id: String,
+    pub experiment: String,
+    pub snapshot: ExperimentSnapshot,
+    pub host: String,
+    pub start: String,
+    /// Absent while the run is in progress, or if it was interrupted
+    pub end: Option<String>,
+    /// Exit code of the run, non-zero if any of its trials failed
+    pub exit_code: Option<i32>,
+    /// Directory holding the stdout and stderr of every trial
+    pub logs: PathBuf,
+}
+
+impl Run {
+    pub(crate) fn new(
+        etna_config: &EtnaConfig,
+        experiment_name: &str,
+        snapshot: &ExperimentSnapshot,
+    ) -> anyhow::Result<Self> {
+        let start = chrono::Utc::now();
+        let id = format!("{}-{}", start.format("%Y%m%dT%H%M%S"), std::process::id());
+        let logs = etna_config.runs_path().join(&id);
+
+        std::fs::create_dir_all(&logs).context(format!(
+            "Failed to create the log directory '{}'",
+            logs.display()
+        ))?;
+
+        Ok(Self {
+            id,
+            experiment: experiment_name.to_string(),
+            snapshot: snapshot.clone(),
+            host: gethostname::gethostname().to_string_lossy().to_string(),
+            start: start.to_rfc3339(),
+            end: None,
+            exit_code: None,
+            logs,
+        })
+    }
+
+    /// Paths of the stdout and stderr logs of a trial
+    pub(crate) fn trial_logs(&self, trial: &Trial) -> (PathBuf, PathBuf) {
+        let dir = self.logs.join(trial.task.id());
+        (
+            dir.join(format!("{}.stdout", trial.trial)),
+            dir.join(format!("{}.stderr", trial.trial)),
+        )
+    }
+
+    pub(crate) fn finish(&mut self, exit_code: i32) {
+        self.end = Some(chrono::Utc::now().to_rfc3339());
+        self.exit_code = Some(exit_code);
+    }
+}
+
 pub(crate) trait Queriable {
     fn query(&self, store: &Store) -> anyhow::Result<Vec<String>>;
 }
@@ -0,0 +1,510 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::warn;
+
+use crate::{
+    config::{EtnaConfig, ExperimentConfig},
+    experiment, git_driver,
+    store::Store,
+    workload::{self, Workload},
+};
+
+/// Workload Transaction
+/// A change to the workloads of an experiment, keeping the workload directories, `config.toml`,
+/// the git history and the store in sync. Nothing is final until [`Transaction::commit`]
+/// succeeds; a transaction that fails or is dropped before undoes every step it took.
+pub(crate) struct Transaction<'a> {
+    etna_config: &'a EtnaConfig,
+    pub experiment_config: ExperimentConfig,
+    /// Content of `config.toml` before the transaction
+    original_config: String,
+    /// Head of the experiment repository before the transaction
+    original_head: Option<String>,
+    steps: Vec<Step>,
+    /// Descriptions of the changes for the commit message, e.g. `add workload 'Coq/BST'`
+    changes: Vec<String>,
+    /// Paths to commit, relative to the experiment directory
+    paths: Vec<PathBuf>,
+    committed: bool,
+}
+
+/// A change to the experiment directory, along with what is needed to undo it
+enum Step {
+    Created(PathBuf),
+    /// The directory is kept in a backup until the transaction commits
+    Removed {
+        path: PathBuf,
+        backup: PathBuf,
+    },
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// The tracked files of the experiment were brought from one tree to another
+    Changed {
+        from: String,
+        to: String,
+    },
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn begin(
+        etna_config: &'a EtnaConfig,
+        experiment_config: ExperimentConfig,
+    ) -> anyhow::Result<Self> {
+        let config_path = experiment_config.path.join("config.toml");
+        let original_config =
+            std::fs::read_to_string(&config_path).context("Failed to read config file")?;
+        let original_head = git_driver::head_hash(&experiment_config.path).ok();
+
+        Ok(Self {
+            etna_config,
+            experiment_config,
+            original_config,
+            original_head,
+            steps: vec![],
+            changes: vec![],
+            paths: vec![PathBuf::from("config.toml")],
+            committed: false,
+        })
+    }
+
+    /// Adds a workload whose directory is filled by `fill`, e.g. by copying another workload
+    pub(crate) fn add(
+        &mut self,
+        workload: Workload,
+        fill: impl FnOnce(&Path) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let key = format!("{}/{}", workload.language, workload.name);
+
+        if self
+            .experiment_config
+            .find_workload(&workload.language, &workload.name)
+            .is_ok()
+        {
+            anyhow::bail!("Workload '{key}' already exists");
+        }
+
+        let path = workload.path(&self.experiment_config.path);
+        if path.exists() {
+            anyhow::bail!(
+                "Directory '{}' already exists, remove it or choose another name",
+                path.display()
+            );
+        }
+
+        // Recorded first, so that a partially filled directory is removed as well
+        self.steps.push(Step::Created(path.clone()));
+        fill(&path)?;
+
+        self.paths.push(workload.relative_path());
+        self.changes.push(format!("add workload '{key}'"));
+        self.experiment_config.workloads.push(workload);
+
+        Ok(())
+    }
+
+    /// Removes a workload, along with its entry in the run matrix
+    pub(crate) fn remove(&mut self, language: &str, name: &str) -> anyhow::Result<Workload> {
+        let workload = self
+            .experiment_config
+            .find_workload(language, name)?
+            .clone();
+        let key = format!("{}/{}", workload.language, workload.name);
+
+        let path = workload.path(&self.experiment_config.path);
+        if path.exists() {
+            let backup = self.backup_path(&workload);
+            move_dir(&path, &backup)?;
+            self.steps.push(Step::Removed { path, backup });
+        }
+
+        self.experiment_config
+            .workloads
+            .retain(|w| !same(w, &workload));
+        self.experiment_config
+            .matrix
+            .workloads
+            .retain(|k, _| !k.eq_ignore_ascii_case(&key));
+
+        self.paths.push(workload.relative_path());
+        self.changes.push(format!("remove workload '{key}'"));
+
+        Ok(workload)
+    }
+
+    /// Brings the files of a workload from the tree `from` to the tree `to` in place, see
+    /// [`git_driver::apply_tree_diff`]. The entry of the workload is returned, e.g. to
+    /// record its new provenance.
+    pub(crate) fn update(
+        &mut self,
+        language: &str,
+        name: &str,
+        from: &str,
+        to: &str,
+    ) -> anyhow::Result<&mut Workload> {
+        let workload = self
+            .experiment_config
+            .find_workload(language, name)?
+            .clone();
+        let key = format!("{}/{}", workload.language, workload.name);
+
+        // Recorded first, so that a partially applied diff is undone as well
+        self.steps.push(Step::Changed {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+        git_driver::apply_tree_diff(&self.experiment_config.path, from, to)
+            .context(format!("Failed to update the files of '{key}'"))?;
+
+        self.paths.push(workload.relative_path());
+        self.changes.push(format!("update workload '{key}'"));
+
+        self.experiment_config
+            .workloads
+            .iter_mut()
+            .find(|w| same(w, &workload))
+            .context(format!("Workload '{key}' does not exist"))
+    }
+
+    /// Renames a workload or moves it to another language, keeping its provenance and
+    /// its entry in the run matrix
+    pub(crate) fn rename(
+        &mut self,
+        language: &str,
+        name: &str,
+        new_language: &str,
+        new_name: &str,
+    ) -> anyhow::Result<()> {
+        let workload = self
+            .experiment_config
+            .find_workload(language, name)?
+            .clone();
+
+        // Both end up in the path of the workload, e.g. `../escaped` would leave `workloads`
+        if new_language.is_empty() || !new_language.chars().all(|c| c.is_ascii_alphanumeric()) {
+            anyhow::bail!("Invalid language '{new_language}', use only letters and digits");
+        }
+        workload::check_name(new_language, new_name)?;
+
+        let renamed = Workload {
+            language: new_language.to_string(),
+            name: new_name.to_string(),
+            source: workload.source.clone(),
+        };
+
+        let key = format!("{}/{}", workload.language, workload.name);
+        let new_key = format!("{}/{}", renamed.language, renamed.name);
+
+        if key == new_key {
+            anyhow::bail!("Workload '{key}' is already named '{new_key}'");
+        }
+        if self
+            .experiment_config
+            .find_workload(new_language, new_name)
+            .is_ok_and(|w| !same(w, &workload))
+        {
+            anyhow::bail!("Workload '{new_key}' already exists");
+        }
+
+        let from = workload.path(&self.experiment_config.path);
+        let to = renamed.path(&self.experiment_config.path);
+        if !from.is_dir() {
+            anyhow::bail!("Workload '{}' not found", from.display());
+        }
+        if to.exists() && !key.eq_ignore_ascii_case(&new_key) {
+            anyhow::bail!("Directory '{}' already exists", to.display());
+        }
+
+        move_dir(&from, &to)?;
+        self.steps.push(Step::Moved { from, to });
+
+        for w in self.experiment_config.workloads.iter_mut() {
+            if same(w, &workload) {
+                *w = renamed.clone();
+            }
+        }
+
+        let matrix = &mut self.experiment_config.matrix.workloads;
+        if let Some(old) = matrix
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(&key))
+            .cloned()
+        {
+            if let Some(entry) = matrix.remove(&old) {
+                matrix.insert(new_key.clone(), entry);
+            }
+        }
+
+        self.paths.push(workload.relative_path());
+        self.paths.push(renamed.relative_path());
+        self.changes
+            .push(format!("move workload '{key}' to '{new_key}'"));
+
+        Ok(())
+    }
+
+    /// Writes the config file, commits the changes and records the new snapshot of the
+    /// experiment in the store
+    pub(crate) fn commit(mut self) -> anyhow::Result<()> {
+        let experiment_path = self.experiment_config.path.clone();
+
+        // Write the updated config file
+        std::fs::write(
+            experiment_path.join("config.toml"),
+            toml::to_string(&self.experiment_config)
+                .context("Failed to serialize configuration")?,
+        )
+        .context("Failed to write config file")?;
+
+        // Create a commit
+        let message = format!("automated commit: {}", self.changes.join(", "));
+        git_driver::commit_paths(
+            &experiment_path,
+            &self.paths,
+            &message,
+            &self.etna_config.git,
+        )
+        .with_context(|| format!("Failed to commit '{}'", self.changes.join(", ")))?;
+
+        // Add the snapshot to the store
+        Store::update(&self.etna_config.store_path(), |store| {
+            let snapshot = store.take_snapshot(self.etna_config, &self.experiment_config)?;
+
+            store.experiments.insert(experiment::Experiment {
+                name: self.experiment_config.name.clone(),
+                id: snapshot.experiment.clone(),
+                description: self.experiment_config.description.clone(),
+                path: experiment_path,
+                snapshot,
+            });
+
+            Ok(())
+        })?;
+
+        self.committed = true;
+
+        // The removed directories are gone for good
+        let backups = self.backup_root();
+        if backups.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&backups) {
+                warn!(
+                    "Failed to delete the backups in '{}': {e}",
+                    backups.display()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Undoes the steps in reverse order, then restores the config file and the head
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        while let Some(step) = self.steps.pop() {
+            match step {
+                Step::Created(path) => {
+                    if path.exists() {
+                        std::fs::remove_dir_all(&path)
+                            .context(format!("Failed to remove '{}'", path.display()))?;
+                    }
+                }
+                Step::Removed { path, backup } => move_dir(&backup, &path)?,
+                Step::Moved { from, to } => move_dir(&to, &from)?,
+                Step::Changed { from, to } => {
+                    git_driver::apply_tree_diff(&self.experiment_config.path, &to, &from)
+                        .context("Failed to restore the updated files")?
+                }
+            }
+        }
+
+        let backups = self.backup_root();
+        if backups.exists() {
+            std::fs::remove_dir_all(&backups)
+                .context(format!("Failed to remove '{}'", backups.display()))?;
+        }
+
+        let experiment_path = &self.experiment_config.path;
+        std::fs::write(experiment_path.join("config.toml"), &self.original_config)
+            .context("Failed to restore config file")?;
+
+        if let Some(head) = &self.original_head {
+            if git_driver::head_hash(experiment_path).ok().as_ref() != Some(head) {
+                git_driver::reset_head(experiment_path, head)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removed workloads are kept in the git directory until the transaction commits, where
+    /// they are neither part of the snapshot nor of the commit
+    fn backup_path(&self, workload: &Workload) -> PathBuf {
+        self.backup_root().join(workload.relative_path())
+    }
+
+    fn backup_root(&self) -> PathBuf {
+        self.experiment_config
+            .path
+            .join(".git")
+            .join(format!("etna-backup-{}", std::process::id()))
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+
+        if let Err(e) = self.rollback() {
+            warn!(
+                "Failed to undo the changes to '{}': {e:#}",
+                self.experiment_config.path.display()
+            );
+        }
+    }
+}
+
+/// Whether two entries are the same workload, whatever their provenance
+fn same(a: &Workload, b: &Workload) -> bool {
+    a.language == b.language && a.name == b.name
+}
+
+fn move_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)
+            .context(format!("Failed to create directory '{}'", parent.display()))?;
+    }
+
+    std::fs::rename(from, to).context(format!(
+        "Failed to move '{}' to '{}'",
+        from.display(),
+        to.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GitConfig;
+
+    /// An experiment holding the workload `Coq/sample`, committed in its repository
+    fn experiment(dir: &Path) -> (EtnaConfig, ExperimentConfig) {
+        let etna_dir = dir.join("etna");
+        std::fs::create_dir_all(&etna_dir).unwrap();
+        let etna_config = EtnaConfig {
+            repo_dir: etna_dir.join("etna"),
+            venv_dir: etna_dir.join(".venv"),
+            etna_dir,
+            branch: "main".to_string(),
+            configured: true,
+            save_etna_patch: false,
+            git: GitConfig {
+                name: Some("etna".to_string()),
+                email: Some("etna@localhost".to_string()),
+                message_template: None,
+            },
+        };
+
+        let path = dir.join("exp");
+        let mut experiment_config = ExperimentConfig::new("exp", "", path.clone());
+        experiment_config.workloads.push(Workload {
+            language: "Coq".to_string(),
+            name: "sample".to_string(),
+            source: None,
+        });
+
+        let sources = path
+            .join("workloads")
+            .join("Coq")
+            .join("sample")
+            .join("Src");
+        std::fs::create_dir_all(&sources).unwrap();
+        std::fs::write(sources.join("Impl.v"), "Definition x := 1.").unwrap();
+        std::fs::write(
+            path.join("config.toml"),
+            toml::to_string(&experiment_config).unwrap(),
+        )
+        .unwrap();
+
+        let repo = git2::Repository::init(&path).unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("etna", "etna@localhost").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+
+        (etna_config, experiment_config)
+    }
+
+    #[test]
+    fn rename_rejects_targets_outside_the_workloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let (etna_config, experiment_config) = experiment(dir.path());
+        let path = experiment_config.path.clone();
+        let config = std::fs::read_to_string(path.join("config.toml")).unwrap();
+
+        for (language, name) in [
+            ("..", "escaped"),
+            ("", "escaped"),
+            ("Coq", ".."),
+            ("Coq", "."),
+            ("Coq", "../escaped"),
+            ("Coq", "bad-name"),
+        ] {
+            let mut transaction = Transaction::begin(
+                &etna_config,
+                ExperimentConfig::from_path(path.clone()).unwrap(),
+            )
+            .unwrap();
+            assert!(
+                transaction.rename("Coq", "sample", language, name).is_err(),
+                "renamed to '{language}/{name}'"
+            );
+            assert_eq!(transaction.experiment_config.workloads[0].name, "sample");
+        }
+
+        assert!(path.join("workloads").join("Coq").join("sample").is_dir());
+        assert!(!path.join("escaped").exists());
+        assert!(!dir.path().join("escaped").exists());
+        assert_eq!(
+            std::fs::read_to_string(path.join("config.toml")).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn failed_rename_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (etna_config, experiment_config) = experiment(dir.path());
+        let path = experiment_config.path.clone();
+        let config = std::fs::read_to_string(path.join("config.toml")).unwrap();
+        let head = git_driver::head_hash(&path).unwrap();
+
+        // The rename is committed, then recording the snapshot fails
+        std::fs::write(etna_config.store_path(), "{bad").unwrap();
+
+        let mut transaction = Transaction::begin(&etna_config, experiment_config).unwrap();
+        transaction
+            .rename("Coq", "sample", "Coq", "renamed")
+            .unwrap();
+        assert!(transaction.commit().is_err());
+
+        let workloads = path.join("workloads").join("Coq");
+        assert!(workloads
+            .join("sample")
+            .join("Src")
+            .join("Impl.v")
+            .is_file());
+        assert!(!workloads.join("renamed").exists());
+        assert_eq!(
+            std::fs::read_to_string(path.join("config.toml")).unwrap(),
+            config
+        );
+        assert_eq!(git_driver::head_hash(&path).unwrap(), head);
+    }
+}
@@ -1,7 +1,804 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
 use serde_derive::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::{
+    catalog,
+    config::{EtnaConfig, ExperimentConfig},
+    git_driver, marker, scheduler,
+    store::Store,
+};
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct Workload {
     pub language: String,
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+/// Provenance of a workload, recorded when it is added or updated
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Source {
+    /// Path or url of the repository the workload was copied from
+    pub repo: String,
+    /// Commit of the repository at the time of the copy, if it is a git repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Tree hash of the copied workload, the common ancestor of `workload update`
+    pub tree: String,
+}
+
+impl Workload {
+    /// Path of the workload inside an experiment or an etna repository
+    pub(crate) fn path(&self, root: &Path) -> PathBuf {
+        root.join(self.relative_path())
+    }
+
+    pub(crate) fn relative_path(&self) -> PathBuf {
+        PathBuf::from("workloads")
+            .join(&self.language)
+            .join(&self.name)
+    }
+}
+
+/// Every workload of an etna repository, found in `workloads/<language>/<name>`
+pub(crate) fn available(repo_dir: &Path) -> anyhow::Result<Vec<Workload>> {
+    let mut workloads = vec![];
+
+    for language in subdirectories(&repo_dir.join("workloads"))? {
+        for name in subdirectories(&repo_dir.join("workloads").join(&language))? {
+            workloads.push(Workload {
+                language: language.clone(),
+                name,
+                source: None,
+            });
+        }
+    }
+
+    workloads.sort_by(|a, b| a.language.cmp(&b.language).then(a.name.cmp(&b.name)));
+    Ok(workloads)
+}
+
+/// Names of the directories in `path`, skipping hidden ones
+fn subdirectories(path: &Path) -> anyhow::Result<Vec<String>> {
+    let entries =
+        std::fs::read_dir(path).context(format!("Failed to read '{}'", path.display()))?;
+
+    let mut names = vec![];
+    for entry in entries {
+        let path = entry.context("Failed to read directory entry")?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if path.is_dir() && !name.starts_with('.') {
+            names.push(name);
+        }
+    }
+
+    Ok(names)
+}
+
+/// Strategies, mutant variants and properties found in the sources of a workload
+#[derive(Debug, Default)]
+pub(crate) struct WorkloadContents {
+    pub strategies: Vec<String>,
+    pub variants: Vec<String>,
+    pub properties: Vec<String>,
+}
+
+/// Directories holding build artifacts, they are never scanned
+const BUILD_DIRS: [&str; 5] = [
+    "_build",
+    "compiled",
+    "dist-newstyle",
+    ".stack-work",
+    "target",
+];
+
+/// Languages supported by etna, each has a workload template
+pub(crate) const LANGUAGES: [&str; 3] = ["Coq", "Haskell", "Racket"];
+
+/// Source file extensions of the supported languages
+const SOURCE_EXTENSIONS: [&str; 3] = ["v", "hs", "rkt"];
+
+/// Scans the sources of a workload
+/// - Strategies are the source files under a directory named `Strategies`, `Strategy`, etc.
+/// - Properties are the `prop_` definitions in the `Spec` files
+/// - Variants are the names of the mutant markers in the remaining sources, see [`marker::parse`]
+pub(crate) fn inspect(path: &Path) -> anyhow::Result<WorkloadContents> {
+    if !path.is_dir() {
+        anyhow::bail!("Workload '{}' not found", path.display());
+    }
+
+    let mut contents = WorkloadContents::default();
+
+    for file in source_files(path)? {
+        let relative = file.strip_prefix(path).unwrap_or(&file);
+        let stem = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if is_strategy(relative) {
+            contents.strategies.push(stem);
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&file)
+            .context(format!("Failed to read '{}'", file.display()))?;
+
+        if stem.to_lowercase() == "spec" {
+            contents.properties.extend(properties(&source));
+        }
+
+        let (mutations, _) = marker::parse(&source);
+        contents.variants.extend(
+            mutations
+                .into_iter()
+                .flat_map(|m| m.variants)
+                .map(|v| v.name),
+        );
+    }
+
+    for names in [
+        &mut contents.strategies,
+        &mut contents.variants,
+        &mut contents.properties,
+    ] {
+        names.sort();
+        names.dedup();
+    }
+
+    Ok(contents)
+}
+
+/// All the source files of a workload, skipping hidden and build directories
+pub(crate) fn source_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut stack = vec![path.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries =
+            std::fs::read_dir(&dir).context(format!("Failed to read '{}'", dir.display()))?;
+
+        for entry in entries {
+            let path = entry.context("Failed to read directory entry")?.path();
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if path.is_dir() {
+                if !name.starts_with('.') && !BUILD_DIRS.contains(&name.as_str()) {
+                    stack.push(path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()))
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// The mutations of a source file of a workload
+#[derive(Debug)]
+pub(crate) struct MarkedFile {
+    /// Path of the file, relative to the workload root
+    pub path: PathBuf,
+    pub mutations: Vec<marker::Mutation>,
+    pub errors: Vec<marker::MarkerError>,
+}
+
+/// Parses the mutant markers of the sources of a workload, skipping the strategies
+pub(crate) fn marked_files(path: &Path) -> anyhow::Result<Vec<MarkedFile>> {
+    let mut files = vec![];
+
+    for file in source_files(path)? {
+        let relative = file.strip_prefix(path).unwrap_or(&file).to_path_buf();
+        if is_strategy(&relative) {
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&file)
+            .context(format!("Failed to read '{}'", file.display()))?;
+        let (mutations, errors) = marker::parse(&source);
+
+        files.push(MarkedFile {
+            path: relative,
+            mutations,
+            errors,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Copies a workload to `output` with a single mutant variant applied to its sources,
+/// returns the files the variant changed. `output` must not exist.
+pub(crate) fn materialize(
+    path: &Path,
+    output: &Path,
+    variant: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let files = marked_files(path)?;
+
+    // Markers that do not parse cannot be applied reliably
+    let errors = files
+        .iter()
+        .flat_map(|f| {
+            f.errors
+                .iter()
+                .map(move |e| format!("{}:{}: {}", f.path.display(), e.line, e.message))
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "The mutant markers of '{}' are malformed:\n  {}",
+            path.display(),
+            errors.join("\n  ")
+        );
+    }
+
+    let mut variants = files
+        .iter()
+        .flat_map(|f| f.mutations.iter())
+        .flat_map(|m| m.variants.iter().map(|v| v.name.as_str()))
+        .collect::<Vec<_>>();
+    variants.sort();
+    variants.dedup();
+
+    if !variants.contains(&variant) {
+        anyhow::bail!(
+            "Unknown variant '{variant}' for '{}'{}",
+            path.display(),
+            catalog::hint(variant, &variants)
+        );
+    }
+
+    copy_dir(path, output)?;
+
+    let mut applied = vec![];
+    for file in files.iter() {
+        let path = output.join(&file.path);
+        let source = std::fs::read_to_string(&path)
+            .context(format!("Failed to read '{}'", path.display()))?;
+
+        if let Some(mutated) = marker::apply(&source, &file.mutations, variant) {
+            std::fs::write(&path, mutated)
+                .context(format!("Failed to write '{}'", path.display()))?;
+            applied.push(file.path.clone());
+        }
+    }
+
+    Ok(applied)
+}
+
+/// Copies a workload directory, skipping build artifacts, hidden version control directories
+/// and the files ignored by `.gitignore`. The copy is made next to `to` and renamed into
+/// place once complete, so a failure leaves nothing behind. `to` must not exist.
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if to.exists() {
+        anyhow::bail!("'{}' already exists", to.display());
+    }
+    if !from.is_dir() {
+        anyhow::bail!("'{}' is not a directory", from.display());
+    }
+
+    let parent = to.parent().context("Failed to get parent directory")?;
+    std::fs::create_dir_all(parent)
+        .context(format!("Failed to create directory '{}'", parent.display()))?;
+
+    let name = to
+        .file_name()
+        .context("Failed to get directory name")?
+        .to_string_lossy();
+    let staging = parent.join(format!(".{name}.tmp-{}", std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging).context("Failed to clear the staging directory")?;
+    }
+
+    let copied = copy_entries(from, &staging).and_then(|()| {
+        std::fs::rename(&staging, to).context(format!(
+            "Failed to move '{}' to '{}'",
+            staging.display(),
+            to.display()
+        ))
+    });
+
+    if copied.is_err() && staging.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+
+    copied.context(format!(
+        "Failed to copy '{}' to '{}'",
+        from.display(),
+        to.display()
+    ))
+}
+
+fn copy_entries(from: &Path, to: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir(to).context(format!("Failed to create '{}'", to.display()))?;
+
+    let walker = ignore::WalkBuilder::new(from)
+        .hidden(false)
+        .git_global(false)
+        // A workload need not be in a git repository for its `.gitignore` to apply
+        .require_git(false)
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+
+            !(is_dir && (name == ".git" || BUILD_DIRS.contains(&name.as_ref())))
+        })
+        .build();
+
+    for entry in walker {
+        let entry = entry.context("Failed to walk the workload directory")?;
+        let relative = entry.path().strip_prefix(from).unwrap_or(entry.path());
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+
+        let target = to.join(relative);
+        let file_type = entry.file_type().context(format!(
+            "Failed to get the type of '{}'",
+            entry.path().display()
+        ))?;
+
+        if file_type.is_dir() {
+            std::fs::create_dir(&target)
+                .context(format!("Failed to create '{}'", target.display()))?;
+        } else if file_type.is_symlink() {
+            copy_link(entry.path(), &target)?;
+        } else {
+            // Permissions, including the executable bit of scripts, are copied along
+            std::fs::copy(entry.path(), &target)
+                .context(format!("Failed to copy '{}'", entry.path().display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies a symbolic link as a link, so that it keeps pointing at the same target
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> anyhow::Result<()> {
+    let link =
+        std::fs::read_link(from).context(format!("Failed to read link '{}'", from.display()))?;
+    std::os::unix::fs::symlink(&link, to)
+        .context(format!("Failed to create link '{}'", to.display()))
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> anyhow::Result<()> {
+    std::fs::copy(from, to).context(format!("Failed to copy '{}'", from.display()))?;
+    Ok(())
+}
+
+/// Whether a file, relative to the workload root, is a strategy
+pub(crate) fn is_strategy(relative: &Path) -> bool {
+    relative.parent().is_some_and(|parent| {
+        parent.components().any(|component| {
+            component
+                .as_os_str()
+                .to_string_lossy()
+                .to_lowercase()
+                .starts_with("strateg")
+        })
+    })
+}
+
+/// Identifiers starting with `prop_`
+pub(crate) fn properties(source: &str) -> Vec<String> {
+    source
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '\''))
+        .filter(|token| token.starts_with("prop_") && token.len() > "prop_".len())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Provenance of the copy of a workload in a source repository. Its tree is archived,
+/// so that it can serve as the base of a merge even after the repository moves on.
+pub(crate) fn source(
+    etna_config: &EtnaConfig,
+    repo_dir: &Path,
+    workload: &Workload,
+) -> anyhow::Result<Source> {
+    let tree = git_driver::hash(repo_dir, &workload.relative_path().join("*"))?;
+    git_driver::archive_tree(repo_dir, &etna_config.objects_path(), &tree)?;
+
+    Ok(Source {
+        repo: repo_dir.display().to_string(),
+        commit: git_driver::head_hash(repo_dir).ok(),
+        tree,
+    })
+}
+
+/// Where `workload add --from` imports a workload from: a local directory, or a git
+/// repository at an optional revision, e.g. `https://github.com/org/heap.git#v1.0`
+#[derive(Debug)]
+pub(crate) struct Origin {
+    pub location: String,
+    pub rev: Option<String>,
+}
+
+impl Origin {
+    pub(crate) fn parse(value: &str) -> Self {
+        match value.rsplit_once('#') {
+            Some((location, rev)) if !rev.is_empty() => Self {
+                location: location.to_string(),
+                rev: Some(rev.to_string()),
+            },
+            _ => Self {
+                location: value.trim_end_matches('#').to_string(),
+                rev: None,
+            },
+        }
+    }
+
+    /// Fetches the origin, cloning it unless it is a local directory taken as it is
+    pub(crate) fn fetch(&self) -> anyhow::Result<Checkout> {
+        let local = Path::new(&self.location);
+
+        if local.is_dir() && self.rev.is_none() {
+            let root = local
+                .canonicalize()
+                .context(format!("Failed to resolve '{}'", local.display()))?;
+
+            return Ok(Checkout {
+                repo: root.display().to_string(),
+                commit: git_driver::enclosing_head_hash(&root),
+                root,
+                _clone: None,
+            });
+        }
+
+        // Local repositories are cloned too, so that checking out a revision leaves them untouched
+        let repo = match local.canonicalize() {
+            Ok(path) => path.display().to_string(),
+            Err(_) => self.location.clone(),
+        };
+        // Removed when dropped, including when the clone fails
+        let clone = scheduler::private_dir("etna-import-")
+            .context("Failed to create the import directory")?;
+        let root = clone.path().to_path_buf();
+        let commit = git_driver::clone_at(&repo, &root, self.rev.as_deref())?;
+
+        Ok(Checkout {
+            repo,
+            commit: Some(commit),
+            root,
+            _clone: Some(clone),
+        })
+    }
+}
+
+/// A fetched origin, removed when dropped if it is a temporary clone
+#[derive(Debug)]
+pub(crate) struct Checkout {
+    /// Path or url the workload is recorded to come from
+    pub repo: String,
+    pub commit: Option<String>,
+    pub root: PathBuf,
+    /// The temporary clone holding `root`, if any
+    _clone: Option<TempDir>,
+}
+
+impl Checkout {
+    /// The directory of a workload in the checkout. It is either an etna-like repository
+    /// holding the workload in `workloads/<language>/<name>`, or the workload itself.
+    pub(crate) fn workload_dir(&self, workload: &Workload) -> PathBuf {
+        let nested = workload.path(&self.root);
+
+        if nested.is_dir() {
+            nested
+        } else {
+            self.root.clone()
+        }
+    }
+
+    /// Provenance of a workload imported from the checkout into an experiment. The tree
+    /// is hashed from the experiment's copy, as the checkout need not be a git repository.
+    pub(crate) fn source(
+        &self,
+        etna_config: &EtnaConfig,
+        experiment_path: &Path,
+        workload: &Workload,
+    ) -> anyhow::Result<Source> {
+        let tree = git_driver::hash(experiment_path, &workload.relative_path().join("*"))?;
+        git_driver::archive_tree(experiment_path, &etna_config.objects_path(), &tree)?;
+
+        Ok(Source {
+            repo: self.repo.clone(),
+            commit: self.commit.clone(),
+            tree,
+        })
+    }
+}
+
+/// Extension of the source files of a language, if it is one of the supported ones
+pub(crate) fn extension(language: &str) -> Option<&'static str> {
+    match language.to_lowercase().as_str() {
+        "coq" => Some("v"),
+        "haskell" => Some("hs"),
+        "racket" => Some("rkt"),
+        _ => None,
+    }
+}
+
+/// Checks that `name` can name a workload of `language`: the templates of the supported
+/// languages put it in module paths and package names, and it is always a directory name
+pub(crate) fn check_name(language: &str, name: &str) -> anyhow::Result<()> {
+    let word = |w: &str| !w.is_empty() && w.chars().all(|c| c.is_ascii_alphanumeric());
+    let (valid, rule) = match language.to_lowercase().as_str() {
+        // A logical path in `-Q Src <name>` and `From <name> Require Import`
+        "coq" => (
+            name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "a Coq identifier, a letter followed by letters, digits and '_'",
+        ),
+        // A Cabal package name
+        "haskell" => (
+            name.split('-')
+                .all(|w| word(w) && w.chars().any(|c| c.is_ascii_alphabetic())),
+            "a Cabal package name, words of letters and digits separated by '-', each with a letter",
+        ),
+        // A Racket collection name, which is also a safe directory name for other languages
+        language => (
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+')),
+            if language == "racket" {
+                "a Racket collection name, made of letters, digits, '_', '-' and '+'"
+            } else {
+                "made of letters, digits, '_', '-' and '+'"
+            },
+        ),
+    };
+
+    if !valid {
+        anyhow::bail!("Invalid {language} workload name '{name}', it must be {rule}");
+    }
+
+    Ok(())
+}
+
+/// Checks that a directory has the layout of a workload of a language: sources in that
+/// language, properties in a `Spec` file and at least one strategy.
+pub(crate) fn validate(path: &Path, language: &str) -> anyhow::Result<WorkloadContents> {
+    let contents = inspect(path)?;
+
+    let extension = extension(language);
+    let has_sources = source_files(path)?.iter().any(|file| {
+        extension.is_none_or(|extension| file.extension().is_some_and(|ext| ext == extension))
+    });
+
+    if !has_sources {
+        anyhow::bail!("'{}' has no {language} sources", path.display());
+    }
+    if contents.properties.is_empty() {
+        anyhow::bail!(
+            "'{}' has no properties, expected 'prop_' definitions in a Spec file",
+            path.display()
+        );
+    }
+    if contents.strategies.is_empty() {
+        anyhow::bail!(
+            "'{}' has no strategies, expected them in a 'Strategies' directory",
+            path.display()
+        );
+    }
+
+    Ok(contents)
+}
+
+/// How an experiment's copy of a workload relates to its copy in etna
+#[derive(Debug)]
+pub(crate) struct Drift {
+    /// Tree hash of the experiment's copy
+    pub local: String,
+    /// Tree hash of the etna copy, if the workload still exists in etna
+    pub upstream: Option<String>,
+    /// Tree hash of the etna copy when the workload was last added or updated
+    pub base: Option<String>,
+}
+
+impl Drift {
+    pub(crate) fn status(&self) -> &'static str {
+        let (Some(upstream), Some(base)) = (&self.upstream, &self.base) else {
+            return if self.upstream.is_none() {
+                "not in etna"
+            } else {
+                "unknown base"
+            };
+        };
+
+        match (&self.local != base, upstream != base) {
+            (false, false) => "up to date",
+            (true, false) => "modified locally",
+            (false, true) => "upstream changed",
+            (true, true) => "diverged",
+        }
+    }
+}
+
+/// Compares an experiment's copy of a workload with its etna copy. The trees of the etna
+/// copy and the base are copied into the experiment repository, so they can be merged.
+/// Workloads added before bases were recorded fall back to their first snapshot in the store.
+pub(crate) fn drift(
+    etna_config: &EtnaConfig,
+    experiment_config: &ExperimentConfig,
+    store: &Store,
+    workload: &Workload,
+) -> anyhow::Result<Drift> {
+    let experiment_path = &experiment_config.path;
+    let pattern = workload.relative_path().join("*");
+
+    let local = git_driver::hash(experiment_path, &pattern)?;
+
+    let repo_dir = etna_config.workloads_repo_dir();
+    let upstream = if workload.path(&repo_dir).exists() {
+        let tree = git_driver::hash(&repo_dir, &pattern)?;
+        git_driver::copy_tree(&repo_dir, experiment_path, &tree)?;
+        Some(tree)
+    } else {
+        None
+    };
+
+    let base = workload
+        .source
+        .as_ref()
+        .map(|s| s.tree.clone())
+        .or_else(|| {
+            store
+                .get_experiment_history(&experiment_config.name)
+                .into_iter()
+                .find_map(|(experiment, _)| {
+                    experiment
+                        .snapshot
+                        .workloads
+                        .iter()
+                        .find(|(w, _)| w.language == workload.language && w.name == workload.name)
+                        .map(|(_, hash)| hash.clone())
+                })
+        });
+
+    // The base may only be left in the archive, or in the etna repository
+    let base = match base {
+        Some(base) if git_driver::contains_tree(experiment_path, &base)? => Some(base),
+        Some(base) => [etna_config.objects_path(), repo_dir]
+            .iter()
+            .find(|repo| git_driver::contains_tree(repo, &base).unwrap_or(false))
+            .map(|repo| git_driver::copy_tree(repo, experiment_path, &base))
+            .transpose()?
+            .map(|_| base),
+        None => None,
+    };
+
+    Ok(Drift {
+        local,
+        upstream,
+        base,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coq_names_are_identifiers() {
+        assert!(check_name("Coq", "BST").is_ok());
+        assert!(check_name("Coq", "red_black2").is_ok());
+        assert!(check_name("Coq", "red-black").is_err());
+        assert!(check_name("Coq", "2bst").is_err());
+        assert!(check_name("Coq", "_bst").is_err());
+        assert!(check_name("Coq", "").is_err());
+    }
+
+    #[test]
+    fn haskell_names_are_package_names() {
+        assert!(check_name("Haskell", "BST").is_ok());
+        assert!(check_name("Haskell", "red-black2").is_ok());
+        assert!(check_name("Haskell", "red_black").is_err());
+        assert!(check_name("Haskell", "bst-2").is_err());
+        assert!(check_name("Haskell", "bst-").is_err());
+        assert!(check_name("Haskell", "").is_err());
+    }
+
+    #[test]
+    fn racket_and_other_names_are_directory_names() {
+        for language in ["Racket", "Lean"] {
+            assert!(check_name(language, "red-black_2+").is_ok());
+            assert!(check_name(language, "2bst").is_ok());
+            assert!(check_name(language, "red.black").is_err());
+            assert!(check_name(language, "..").is_err());
+            assert!(check_name(language, "../bst").is_err());
+            assert!(check_name(language, "").is_err());
+        }
+    }
+
+    #[test]
+    fn properties_are_whole_identifiers() {
+        let source =
+            "test (prop_InsertValid, prop_Insert') = prop_Insert x ++ prop_ ++ myprop_Size";
+
+        assert_eq!(
+            properties(source),
+            ["prop_InsertValid", "prop_Insert'", "prop_Insert"]
+        );
+    }
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn copy_skips_ignored_paths_and_build_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("BST");
+        write(&from.join(".gitignore"), "*.vo\nsecret/\n");
+        write(&from.join("Src").join("Impl.v"), "Definition x := 1.");
+        write(&from.join("Src").join("Impl.vo"), "");
+        write(&from.join("secret").join("key"), "");
+        write(&from.join("_build").join("Impl.vo"), "");
+        write(&from.join("dist-newstyle").join("cache"), "");
+        write(&from.join(".git").join("HEAD"), "");
+
+        let to = dir.path().join("copy").join("BST");
+        copy_dir(&from, &to).unwrap();
+
+        assert!(to.join(".gitignore").is_file());
+        assert!(to.join("Src").join("Impl.v").is_file());
+        assert!(!to.join("Src").join("Impl.vo").exists());
+        assert!(!to.join("secret").exists());
+        assert!(!to.join("_build").exists());
+        assert!(!to.join("dist-newstyle").exists());
+        assert!(!to.join(".git").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_copy_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("BST");
+        write(&from.join("Src").join("Impl.v"), "Definition x := 1.");
+        write(&from.join("Spec.v"), "");
+        // A socket cannot be opened for reading, so copying it fails
+        let _socket =
+            std::os::unix::net::UnixListener::bind(from.join("Src").join("sock")).unwrap();
+
+        let parent = dir.path().join("copy");
+        let to = parent.join("BST");
+        let error = copy_dir(&from, &to).unwrap_err();
+
+        assert!(format!("{error:#}").contains("sock"));
+        assert!(!to.exists());
+        assert_eq!(std::fs::read_dir(&parent).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn copy_refuses_an_existing_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("BST");
+        write(&from.join("Spec.v"), "");
+        let to = dir.path().join("copy");
+        write(&to.join("kept"), "");
+
+        assert!(copy_dir(&from, &to).is_err());
+        assert!(to.join("kept").is_file());
+        assert!(!to.join("Spec.v").exists());
+    }
 }
@@ -0,0 +1,120 @@
+//! Test harness running the etna binary against a throwaway home directory, holding
+//! its own etna configuration, store and etna repository.
+
+#![allow(dead_code)]
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+pub struct Etna {
+    dir: tempfile::TempDir,
+}
+
+impl Etna {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let etna_dir = dir.path().join("home").join(".etna");
+        let repo_dir = etna_dir.join("etna");
+        std::fs::create_dir_all(&repo_dir).unwrap();
+
+        // The etna repository only needs a commit to snapshot
+        let repo = git2::Repository::init(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("README.md"), "etna\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("etna", "etna@localhost").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+
+        let config = serde_json::json!({
+            "etna_dir": etna_dir,
+            "repo_dir": repo_dir,
+            "venv_dir": etna_dir.join(".venv"),
+            "branch": "main",
+            "configured": true,
+            "git": { "name": "etna", "email": "etna@localhost" },
+        });
+        std::fs::write(etna_dir.join("config.json"), config.to_string()).unwrap();
+
+        let store = serde_json::json!({
+            "metrics": [],
+            "snapshots": [],
+            "experiments": [],
+            "runs": [],
+        });
+        std::fs::write(etna_dir.join("store.json"), store.to_string()).unwrap();
+        std::fs::create_dir(dir.path().join("tmp")).unwrap();
+
+        Self { dir }
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The etna repository, holding the workloads that can be added to experiments
+    pub fn repo_dir(&self) -> PathBuf {
+        self.root().join("home").join(".etna").join("etna")
+    }
+
+    /// The temporary directory of etna, empty unless etna leaves files behind
+    pub fn tmp_dir(&self) -> PathBuf {
+        self.root().join("tmp")
+    }
+
+    pub fn store_path(&self) -> PathBuf {
+        self.root().join("home").join(".etna").join("store.json")
+    }
+
+    pub fn store(&self) -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(self.store_path()).unwrap()).unwrap()
+    }
+
+    /// Runs etna in `cwd`
+    pub fn run(&self, cwd: &Path, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_etna-cli"))
+            .args(args)
+            .current_dir(cwd)
+            .env("HOME", self.root().join("home"))
+            .env("TMPDIR", self.tmp_dir())
+            .env("RUST_LOG", "info")
+            .env("RUST_BACKTRACE", "0")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs etna in `cwd`, failing the test if it fails
+    pub fn ok(&self, cwd: &Path, args: &[&str]) -> Output {
+        let output = self.run(cwd, args);
+        assert!(
+            output.status.success(),
+            "etna {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    /// Creates an experiment under the root, holding a single Coq workload
+    pub fn experiment(&self, name: &str) -> PathBuf {
+        self.ok(self.root(), &["experiment", "new", name]);
+        let path = self.root().join(name);
+        self.ok(&path, &["workload", "new", "coq", "sample"]);
+        path
+    }
+}
+
+/// Replaces `key = ...` at the top level of the `config.toml` of an experiment
+pub fn set_config(experiment: &Path, key: &str, value: impl Into<toml::Value>) {
+    let path = experiment.join("config.toml");
+    let mut config: toml::Table = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+    config.insert(key.to_string(), value.into());
+    std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
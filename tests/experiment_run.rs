@@ -0,0 +1,135 @@
+//! `etna experiment run` runs the collection script of an experiment from the experiment
+//! directory, whatever the working directory of etna is.
+
+mod common;
+
+use common::{set_config, stderr, Etna};
+
+/// Records the working directory and the environment of the trial next to where it runs
+const SCRIPT: &str = r#"
+import json, os
+with open(f"trial-{os.environ['ETNA_TRIAL']}.json", "w") as f:
+    json.dump({"cwd": os.getcwd(), "env": dict(os.environ)}, f)
+"#;
+
+#[test]
+fn runs_a_custom_script_from_the_experiment_directory() {
+    let etna = Etna::new();
+    let experiment = etna.experiment("exp");
+
+    std::fs::create_dir(experiment.join("scripts")).unwrap();
+    std::fs::write(experiment.join("scripts").join("collect.py"), SCRIPT).unwrap();
+    set_config(&experiment, "script", "scripts/collect.py");
+
+    // A script at the same relative path in the working directory is not the one to run
+    let elsewhere = etna.root().join("elsewhere");
+    std::fs::create_dir_all(elsewhere.join("scripts")).unwrap();
+    std::fs::write(
+        elsewhere.join("scripts").join("collect.py"),
+        "raise SystemExit('ran the script of the working directory')",
+    )
+    .unwrap();
+
+    etna.ok(
+        &elsewhere,
+        &["experiment", "run", "-n", "exp", "--trials", "2"],
+    );
+
+    for trial in 0..2 {
+        let record = experiment.join(format!("trial-{trial}.json"));
+        let record: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&record).unwrap()).unwrap();
+
+        assert_eq!(
+            std::fs::canonicalize(record["cwd"].as_str().unwrap()).unwrap(),
+            std::fs::canonicalize(&experiment).unwrap()
+        );
+        assert_eq!(record["env"]["ETNA_TRIAL"], trial.to_string());
+        assert_eq!(record["env"]["ETNA_WORKLOAD"], "sample");
+        assert!(record["env"]["ETNA_EXPERIMENT_ID"].is_string());
+    }
+
+    assert!(!elsewhere.join("trial-0.json").exists());
+
+    // Every trial is recorded in the store, along with the run
+    let store = etna.store();
+    let metrics = store["metrics"].as_array().unwrap();
+    assert_eq!(metrics.len(), 2);
+    assert!(metrics.iter().all(|m| m["data"]["outcome"] == "completed"));
+    assert_eq!(store["runs"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn records_the_custom_script_in_the_snapshot() {
+    let etna = Etna::new();
+    let experiment = etna.experiment("exp");
+
+    std::fs::write(experiment.join("collect.py"), SCRIPT).unwrap();
+    set_config(&experiment, "script", "collect.py");
+
+    etna.ok(
+        etna.root(),
+        &["experiment", "run", "-n", "exp", "--trials", "1"],
+    );
+
+    let store = etna.store();
+    let run = &store["runs"][0];
+    let scripts = run["snapshot"]["scripts"].as_array().unwrap();
+    assert_eq!(scripts.len(), 1);
+    assert_eq!(scripts[0][0], "collect.py");
+}
+
+#[test]
+fn fails_when_the_script_does_not_exist() {
+    let etna = Etna::new();
+    let experiment = etna.experiment("exp");
+    set_config(&experiment, "script", "missing.py");
+
+    let output = etna.run(
+        etna.root(),
+        &["experiment", "run", "-n", "exp", "--trials", "1"],
+    );
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("missing.py' of the experiment exp does not exist"));
+    assert!(etna.store()["metrics"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn native_runner_tests_a_copy_with_the_variant_applied() {
+    let etna = Etna::new();
+    let experiment = etna.experiment("exp");
+    let results = etna.root().join("results");
+    std::fs::create_dir(&results).unwrap();
+
+    let mut runner = toml::Table::new();
+    runner.insert("type".into(), "native".into());
+    runner.insert("build".into(), "touch built".into());
+    runner.insert(
+        "test".into(),
+        r#"test -f built && cp Src/Impl.v "$RESULTS/$ETNA_VARIANT-$ETNA_TRIAL.v" && pwd > "$RESULTS/pwd""#.into(),
+    );
+    set_config(&experiment, "runner", runner);
+
+    let mut env = toml::Table::new();
+    env.insert("RESULTS".into(), results.display().to_string().into());
+    set_config(&experiment, "env", env);
+
+    etna.ok(
+        etna.root(),
+        &["experiment", "run", "-n", "exp", "--trials", "1"],
+    );
+
+    let workload = experiment.join("workloads").join("Coq").join("sample");
+    let original = std::fs::read_to_string(workload.join("Src").join("Impl.v")).unwrap();
+    let tested = std::fs::read_to_string(results.join("insert_1-0.v")).unwrap();
+
+    assert!(original.contains("    T E k E\n"));
+    assert!(!tested.contains("    T E k E\n"));
+    assert!(tested.contains("  | E =>\n    E\n"));
+
+    // The build ran in the scratch copy, which is gone once the trial finishes
+    assert!(!workload.join("built").exists());
+    let scratch = std::fs::read_to_string(results.join("pwd")).unwrap();
+    assert!(!std::path::Path::new(scratch.trim()).exists());
+}
@@ -0,0 +1,152 @@
+//! `etna workload add` either adds the workload completely, or leaves the experiment as
+//! it was.
+
+mod common;
+
+use common::{stderr, Etna};
+
+/// Hash of the head commit of an experiment
+fn head(experiment: &std::path::Path) -> git2::Oid {
+    let repo = git2::Repository::open(experiment).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    head.id()
+}
+
+#[test]
+fn imports_a_local_workload() {
+    let etna = Etna::new();
+    let origin = etna.experiment("origin");
+    let experiment = etna.root().join("exp");
+    etna.ok(etna.root(), &["experiment", "new", "exp"]);
+    let before = head(&experiment);
+
+    let from = origin.join("workloads").join("Coq").join("sample");
+    etna.ok(
+        &experiment,
+        &[
+            "workload",
+            "add",
+            "Coq",
+            "imported",
+            "--from",
+            from.to_str().unwrap(),
+        ],
+    );
+
+    let workload = experiment.join("workloads").join("Coq").join("imported");
+    assert!(workload.join("Src").join("Impl.v").is_file());
+    let config = std::fs::read_to_string(experiment.join("config.toml")).unwrap();
+    assert!(config.contains("imported"));
+    assert_ne!(head(&experiment), before);
+}
+
+#[test]
+fn failure_after_the_copy_restores_the_experiment() {
+    let etna = Etna::new();
+    let origin = etna.experiment("origin");
+    let experiment = etna.root().join("exp");
+    etna.ok(etna.root(), &["experiment", "new", "exp"]);
+
+    let config = std::fs::read_to_string(experiment.join("config.toml")).unwrap();
+    let before = head(&experiment);
+
+    // The workload is copied and committed, then recording the snapshot fails
+    std::fs::write(etna.store_path(), "{bad").unwrap();
+
+    let from = origin.join("workloads").join("Coq").join("sample");
+    let output = etna.run(
+        &experiment,
+        &[
+            "workload",
+            "add",
+            "Coq",
+            "imported",
+            "--from",
+            from.to_str().unwrap(),
+        ],
+    );
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("store"), "{}", stderr(&output));
+    assert_eq!(
+        std::fs::read_to_string(experiment.join("config.toml")).unwrap(),
+        config
+    );
+    assert_eq!(head(&experiment), before);
+    assert!(!experiment
+        .join("workloads")
+        .join("Coq")
+        .join("imported")
+        .exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn failed_copy_leaves_no_workload() {
+    let etna = Etna::new();
+    let origin = etna.experiment("origin");
+    let experiment = etna.root().join("exp");
+    etna.ok(etna.root(), &["experiment", "new", "exp"]);
+
+    let config = std::fs::read_to_string(experiment.join("config.toml")).unwrap();
+    let before = head(&experiment);
+
+    // A socket cannot be copied, the copy fails after the other files are copied
+    let from = origin.join("workloads").join("Coq").join("sample");
+    let _socket = std::os::unix::net::UnixListener::bind(from.join("Src").join("sock")).unwrap();
+
+    let output = etna.run(
+        &experiment,
+        &[
+            "workload",
+            "add",
+            "Coq",
+            "imported",
+            "--from",
+            from.to_str().unwrap(),
+        ],
+    );
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("sock"), "{}", stderr(&output));
+    let coq = experiment.join("workloads").join("Coq");
+    assert!(!coq.join("imported").exists());
+    assert!(std::fs::read_dir(&coq).map_or(true, |entries| entries.count() == 0));
+    assert_eq!(
+        std::fs::read_to_string(experiment.join("config.toml")).unwrap(),
+        config
+    );
+    assert_eq!(head(&experiment), before);
+}
+
+#[test]
+fn imports_a_revision_and_removes_the_clone() {
+    let etna = Etna::new();
+    let origin = etna.experiment("origin");
+    let experiment = etna.root().join("exp");
+    etna.ok(etna.root(), &["experiment", "new", "exp"]);
+
+    // The origin experiment is a repository holding `workloads/Coq/sample`
+    let from = format!("{}#HEAD", origin.display());
+    etna.ok(
+        &experiment,
+        &["workload", "add", "Coq", "sample", "--from", &from],
+    );
+    assert!(experiment
+        .join("workloads")
+        .join("Coq")
+        .join("sample")
+        .join("Src")
+        .join("Impl.v")
+        .is_file());
+
+    let from = format!("{}#missing", origin.display());
+    let output = etna.run(
+        &experiment,
+        &["workload", "add", "Coq", "other", "--from", &from],
+    );
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Revision 'missing' not found"));
+
+    assert_eq!(std::fs::read_dir(etna.tmp_dir()).unwrap().count(), 0);
+}
@@ -0,0 +1,95 @@
+//! `etna workload update` merges the etna copy of a workload into the experiment, or
+//! leaves the experiment as it was.
+
+mod common;
+
+use std::path::Path;
+
+use common::{stderr, Etna};
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+fn head(experiment: &Path) -> git2::Oid {
+    let repo = git2::Repository::open(experiment).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    head.id()
+}
+
+/// An experiment holding the workload `Coq/BST` of the etna repository, which has moved
+/// on since it was added
+fn experiment(etna: &Etna) -> std::path::PathBuf {
+    let bst = etna.repo_dir().join("workloads").join("Coq").join("BST");
+    write(&bst.join("Src").join("Impl.v"), "Definition x := 1.\n");
+    write(&bst.join("Src").join("Spec.v"), "Definition prop_X := x.\n");
+    write(&bst.join("Strategies").join("Gen.v"), "Check prop_X.\n");
+
+    etna.ok(etna.root(), &["experiment", "new", "exp"]);
+    let experiment = etna.root().join("exp");
+    etna.ok(&experiment, &["workload", "add", "coq", "bst"]);
+
+    write(&bst.join("Src").join("Impl.v"), "Definition x := 2.\n");
+    write(&bst.join("Src").join("Extra.v"), "Definition y := 3.\n");
+
+    experiment
+}
+
+#[test]
+fn pulls_the_changes_of_etna() {
+    let etna = Etna::new();
+    let experiment = experiment(&etna);
+    let before = head(&experiment);
+
+    etna.ok(&experiment, &["workload", "update", "coq", "bst"]);
+
+    let src = experiment
+        .join("workloads")
+        .join("Coq")
+        .join("BST")
+        .join("Src");
+    assert_eq!(
+        std::fs::read_to_string(src.join("Impl.v")).unwrap(),
+        "Definition x := 2.\n"
+    );
+    assert!(src.join("Extra.v").is_file());
+    assert_ne!(head(&experiment), before);
+}
+
+#[test]
+fn failed_update_leaves_the_experiment_untouched() {
+    let etna = Etna::new();
+    let experiment = experiment(&etna);
+
+    let config = std::fs::read_to_string(experiment.join("config.toml")).unwrap();
+    let before = head(&experiment);
+
+    // The files are merged and the config written, then committing them fails
+    write(&experiment.join(".git").join("index.lock"), "");
+
+    let output = etna.run(&experiment, &["workload", "update", "coq", "bst"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Failed to commit"),
+        "{}",
+        stderr(&output)
+    );
+
+    let src = experiment
+        .join("workloads")
+        .join("Coq")
+        .join("BST")
+        .join("Src");
+    assert_eq!(
+        std::fs::read_to_string(src.join("Impl.v")).unwrap(),
+        "Definition x := 1.\n"
+    );
+    assert!(!src.join("Extra.v").exists());
+    assert_eq!(
+        std::fs::read_to_string(experiment.join("config.toml")).unwrap(),
+        config
+    );
+    assert_eq!(head(&experiment), before);
+}